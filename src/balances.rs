@@ -0,0 +1,90 @@
+use serde::Serialize;
+
+use crate::totals;
+
+/// Which `Balances` storage item an encumbrance comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncumbranceKind {
+    /// `Balances::Locks`, the legacy lock API. Counts against the frozen balance.
+    Lock,
+    /// `Balances::Holds`. Held funds are moved out of the free balance into the reserved
+    /// one.
+    Hold,
+    /// `Balances::Freezes`. Counts against the frozen balance like a lock does.
+    Freeze,
+}
+
+/// A lock, hold or freeze on an account's balance, with its amount in the chain's token.
+#[derive(Debug, Clone, Serialize)]
+pub struct Encumbrance {
+    pub kind: EncumbranceKind,
+    /// Pallet that placed it, or "Unknown" if it can't be told from the ID.
+    pub pallet: String,
+    /// Lock ID, or the pallet's own hold or freeze reason.
+    pub reason: String,
+    pub amount: f64,
+}
+
+impl Encumbrance {
+    /// An entry of `Balances::Locks`, identified by its 8 byte ID.
+    pub fn lock(id: &[u8; 8], amount: f64) -> Self {
+        Encumbrance {
+            kind: EncumbranceKind::Lock,
+            pallet: totals::pallet(id).unwrap_or("Unknown").to_string(),
+            reason: String::from_utf8_lossy(id).trim_end().to_string(),
+            amount,
+        }
+    }
+
+    /// An entry of `Balances::Holds` or `Balances::Freezes`, with the pallet and reason
+    /// named by its runtime reason, such as `Preimage` and `Preimage` for
+    /// `Preimage(Preimage)`.
+    pub fn new(kind: EncumbranceKind, pallet: String, reason: String, amount: f64) -> Self {
+        Encumbrance {
+            kind,
+            pallet,
+            reason,
+            amount,
+        }
+    }
+}
+
+/// An account's `AccountData` in the chain's token.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct BalanceSummary {
+    pub free: f64,
+    pub reserved: f64,
+    /// The largest lock or freeze. It applies to free and reserved funds together.
+    pub frozen: f64,
+    /// Free balance that isn't frozen, following `reducible_balance` in pallet-balances
+    /// without the existential deposit: reserved funds count towards the frozen amount
+    /// first.
+    pub transferable: f64,
+}
+
+impl BalanceSummary {
+    pub fn new(free: f64, reserved: f64, frozen: f64) -> Self {
+        let untouchable = (frozen - reserved).max(0.0);
+        BalanceSummary {
+            free,
+            reserved,
+            frozen,
+            transferable: (free - untouchable).max(0.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_funds_count_towards_frozen() {
+        let summary = BalanceSummary::new(100.0, 20.0, 50.0);
+        assert_eq!(summary.transferable, 70.0);
+
+        let summary = BalanceSummary::new(100.0, 60.0, 50.0);
+        assert_eq!(summary.transferable, 100.0);
+    }
+}
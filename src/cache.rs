@@ -0,0 +1,210 @@
+use handlebars::JsonValue;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use subxt::utils::H256;
+
+use crate::referenda::ReferendumStore;
+
+/// Index of chain names to genesis hashes, so cached data can be found offline.
+const CHAINS_FILE: &str = "chains.json";
+
+/// Per chain, the report data of every account last reported online.
+const ACCOUNTS_FILE: &str = "accounts.json";
+
+/// Data kept between runs, one directory per chain named after its genesis hash.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Cache { dir }
+    }
+
+    /// `$XDG_CACHE_HOME/locks-report`, or `~/.cache/locks-report`.
+    pub fn default_dir() -> PathBuf {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .unwrap_or_default()
+            .join("locks-report")
+    }
+
+    fn chain_dir(&self, genesis: H256) -> PathBuf {
+        self.dir.join(format!("{:?}", genesis))
+    }
+
+    /// Referenda of the chain with this genesis hash read by earlier runs.
+    pub fn referenda(&self, genesis: H256) -> ReferendumStore {
+        ReferendumStore::open(self.chain_dir(genesis).join("referenda.json"))
+    }
+
+    /// Keeps the data of a report's accounts, next to that of accounts reported earlier,
+    /// so reports of any of them can be built again with `--offline`.
+    pub fn save_report(
+        &self,
+        chain: &str,
+        genesis: H256,
+        report: &JsonValue,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.chain_dir(genesis).join(ACCOUNTS_FILE);
+        let mut cached = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_else(|| json!({}));
+        merge_accounts(&mut cached, report);
+        write(&path, &serde_json::to_string(&cached)?)?;
+
+        let mut chains = self.chains();
+        if chains.insert(chain.to_string(), genesis) != Some(genesis) {
+            write(
+                &self.dir.join(CHAINS_FILE),
+                &serde_json::to_string(&chains)?,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// A report of `addresses` on `chain` from the data they were last reported online
+    /// with.
+    pub fn load_report(
+        &self,
+        chain: &str,
+        addresses: &[String],
+    ) -> Result<JsonValue, Box<dyn std::error::Error>> {
+        let genesis = self
+            .chains()
+            .get(chain)
+            .copied()
+            .ok_or_else(|| format!("No cached report for chain {}", chain))?;
+        let path = self.chain_dir(genesis).join(ACCOUNTS_FILE);
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let cached: JsonValue = serde_json::from_str(&contents)?;
+        Ok(select_accounts(&cached, addresses)?)
+    }
+
+    fn chains(&self) -> BTreeMap<String, H256> {
+        std::fs::read_to_string(self.dir.join(CHAINS_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Adds the report's accounts to the cached ones, each with the snapshot and clock it was
+/// read with, replacing what was cached for them before.
+fn merge_accounts(cached: &mut JsonValue, report: &JsonValue) {
+    cached["schema_version"] = report["schema_version"].clone();
+    cached["chain"] = report["chain"].clone();
+    for account in report["accounts"].as_array().into_iter().flatten() {
+        let address = account["address"].as_str().unwrap_or_default();
+        cached["accounts"][address] = json!({
+            "snapshot": report["snapshot"],
+            "clock": report["clock"],
+            "data": account["data"],
+        });
+    }
+}
+
+/// A report of the cached `addresses`, in that order. It states the snapshot of the most
+/// recently read of them; accounts read at another block carry their own.
+fn select_accounts(cached: &JsonValue, addresses: &[String]) -> Result<JsonValue, String> {
+    let mut missing = Vec::new();
+    let entries: Vec<_> = addresses
+        .iter()
+        .filter_map(|address| {
+            let entry = cached["accounts"].get(address);
+            if entry.is_none() {
+                missing.push(address.as_str());
+            }
+            entry.map(|entry| (address, entry))
+        })
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Never reported online: {}", missing.join(", ")));
+    }
+    let latest = entries
+        .iter()
+        .map(|(_, entry)| entry)
+        .max_by_key(|entry| entry["snapshot"]["block"].as_u64())
+        .ok_or("No addresses to report")?;
+
+    let accounts: Vec<_> = entries
+        .iter()
+        .map(|(address, entry)| {
+            let mut account = json!({ "address": address, "data": entry["data"] });
+            if entry["snapshot"] != latest["snapshot"] {
+                account["snapshot"] = entry["snapshot"].clone();
+            }
+            account
+        })
+        .collect();
+    Ok(json!({
+        "schema_version": cached["schema_version"],
+        "chain": cached["chain"],
+        "snapshot": latest["snapshot"],
+        "clock": latest["clock"],
+        "accounts": accounts,
+    }))
+}
+
+/// Writes a cache file through a temporary one, so an interrupted run or a concurrent one
+/// never leaves it half written.
+pub fn write(path: &Path, contents: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension(format!("tmp{}", std::process::id()));
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(block: u32, accounts: &[(&str, u32)]) -> JsonValue {
+        json!({
+            "schema_version": 1,
+            "chain": { "name": "polkadot" },
+            "snapshot": { "block": block },
+            "clock": { "block": block },
+            "accounts": accounts
+                .iter()
+                .map(|(address, data)| json!({ "address": address, "data": data }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    #[test]
+    fn offline_reports_keep_requested_accounts_in_order() {
+        let mut cached = json!({});
+        merge_accounts(&mut cached, &report(100, &[("a", 1), ("b", 2)]));
+        let addresses = ["b".to_string(), "a".to_string()];
+        let selected = select_accounts(&cached, &addresses).unwrap();
+        assert_eq!(selected["accounts"][0]["address"], "b");
+        assert_eq!(selected["accounts"][1]["address"], "a");
+        assert_eq!(selected["accounts"][1]["data"], 1);
+
+        let missing = select_accounts(&cached, &["c".to_string()]);
+        assert_eq!(missing.unwrap_err(), "Never reported online: c");
+    }
+
+    #[test]
+    fn accounts_of_earlier_reports_stay_cached() {
+        let mut cached = json!({});
+        merge_accounts(&mut cached, &report(100, &[("a", 1), ("b", 2)]));
+        merge_accounts(&mut cached, &report(200, &[("b", 3)]));
+
+        let addresses = ["a".to_string(), "b".to_string()];
+        let selected = select_accounts(&cached, &addresses).unwrap();
+        // The report is as of the latest block, and says so for the account read earlier
+        assert_eq!(selected["snapshot"]["block"], 200);
+        assert_eq!(selected["accounts"][0]["snapshot"]["block"], 100);
+        assert_eq!(selected["accounts"][1]["data"], 3);
+        assert!(selected["accounts"][1].get("snapshot").is_none());
+    }
+}
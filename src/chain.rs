@@ -0,0 +1,313 @@
+use serde::Deserialize;
+use std::sync::OnceLock;
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::{OnlineClient, PolkadotConfig};
+
+/// Chain used when none is selected.
+pub const DEFAULT_CHAIN: &str = "polkadot";
+
+/// Settings that differ between chains. Every field is optional: anything left out is
+/// read from the chain's properties and runtime constants once connected.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ChainProfile {
+    /// RPC endpoint used when none is given on the command line or in the config.
+    pub url: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+    pub ss58_prefix: Option<u16>,
+    /// Block time in milliseconds, assumed until a block rate can be measured.
+    pub block_time_ms: Option<u64>,
+    /// `VoteLockingPeriod` in blocks.
+    pub vote_locking_period: Option<u32>,
+}
+
+impl ChainProfile {
+    /// The profile shipped for a well-known relay chain. Only facts that don't change
+    /// with runtime upgrades are fixed here; timings always come from the runtime.
+    pub fn builtin(name: &str) -> Option<Self> {
+        let (url, symbol, decimals, ss58_prefix) = match name {
+            "polkadot" => ("wss://rpc.polkadot.io:443", "DOT", 10, 0),
+            "kusama" => ("wss://kusama-rpc.polkadot.io:443", "KSM", 12, 2),
+            "westend" => ("wss://westend-rpc.polkadot.io:443", "WND", 12, 42),
+            _ => return None,
+        };
+        Some(ChainProfile {
+            url: Some(url.to_string()),
+            symbol: Some(symbol.to_string()),
+            decimals: Some(decimals),
+            ss58_prefix: Some(ss58_prefix),
+            ..ChainProfile::default()
+        })
+    }
+
+    /// Fills the fields this profile leaves out from `fallback`.
+    pub fn or(self, fallback: ChainProfile) -> Self {
+        ChainProfile {
+            url: self.url.or(fallback.url),
+            symbol: self.symbol.or(fallback.symbol),
+            decimals: self.decimals.or(fallback.decimals),
+            ss58_prefix: self.ss58_prefix.or(fallback.ss58_prefix),
+            block_time_ms: self.block_time_ms.or(fallback.block_time_ms),
+            vote_locking_period: self.vote_locking_period.or(fallback.vote_locking_period),
+        }
+    }
+}
+
+/// A chain's parameters, resolved against the connected node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chain {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub ss58_prefix: u16,
+    pub block_time_ms: f64,
+    /// `None` on chains without conviction voting.
+    pub vote_locking_period: Option<u32>,
+}
+
+static CHAIN: OnceLock<Chain> = OnceLock::new();
+
+impl Chain {
+    /// Resolves `profile` against the node: the profile's own settings win, then the
+    /// node's `system_properties` and runtime constants fill in the rest.
+    pub async fn load(
+        name: &str,
+        profile: ChainProfile,
+        api: &OnlineClient<PolkadotConfig>,
+        rpc: &LegacyRpcMethods<PolkadotConfig>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let properties = rpc.system_properties().await?;
+        // Relay chains have one token; parachains may list several, the first is native
+        let first = |key: &str| {
+            properties.get(key).and_then(|value| match value {
+                serde_json::Value::Array(values) => values.first().cloned(),
+                value => Some(value.clone()),
+            })
+        };
+
+        let symbol = profile
+            .symbol
+            .or_else(|| first("tokenSymbol")?.as_str().map(str::to_string))
+            .ok_or_else(|| format!("No token symbol for chain {}", name))?;
+        let decimals = profile
+            .decimals
+            .or_else(|| first("tokenDecimals")?.as_u64()?.try_into().ok())
+            .ok_or_else(|| format!("No token decimals for chain {}", name))?;
+        let ss58_prefix = profile
+            .ss58_prefix
+            .or_else(|| first("ss58Format")?.as_u64()?.try_into().ok())
+            .or_else(|| constant(api, "System", "SS58Prefix").and_then(|n| n.try_into().ok()))
+            .ok_or_else(|| format!("No SS58 prefix for chain {}", name))?;
+
+        // BABE chains state their block time; Aura parachains produce a block every two
+        // timestamp periods
+        let block_time_ms = profile
+            .block_time_ms
+            .map(u128::from)
+            .or_else(|| constant(api, "Babe", "ExpectedBlockTime"))
+            .or_else(|| constant(api, "Timestamp", "MinimumPeriod").map(|period| period * 2))
+            .ok_or_else(|| format!("No block time for chain {}", name))?
+            as f64;
+        let vote_locking_period = profile.vote_locking_period.or_else(|| {
+            constant(api, "ConvictionVoting", "VoteLockingPeriod").and_then(|n| n.try_into().ok())
+        });
+
+        let chain = Chain {
+            name: name.to_string(),
+            symbol,
+            decimals,
+            ss58_prefix,
+            block_time_ms,
+            vote_locking_period,
+        };
+        println!(
+            "[Chain] {}: {} with {} decimals, SS58 prefix {}, {:.3}s blocks",
+            chain.name,
+            chain.symbol,
+            chain.decimals,
+            chain.ss58_prefix,
+            chain.block_time_ms / 1000.0
+        );
+        Ok(chain)
+    }
+
+    /// The chain a cached report was built for, to rebuild the report offline. Settings
+    /// the report doesn't record come from `profile`.
+    pub fn from_report(
+        report: &serde_json::Value,
+        profile: ChainProfile,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let recorded = &report["chain"];
+        let name = recorded["name"]
+            .as_str()
+            .ok_or("The cached report names no chain")?;
+        let symbol = recorded["symbol"]
+            .as_str()
+            .map(str::to_string)
+            .or(profile.symbol)
+            .ok_or_else(|| format!("No token symbol for chain {}", name))?;
+        let decimals = recorded["decimals"]
+            .as_u64()
+            .and_then(|n| n.try_into().ok())
+            .or(profile.decimals)
+            .ok_or_else(|| format!("No token decimals for chain {}", name))?;
+        let ss58_prefix = recorded["ss58_prefix"]
+            .as_u64()
+            .and_then(|n| n.try_into().ok())
+            .or(profile.ss58_prefix)
+            .ok_or_else(|| format!("No SS58 prefix for chain {}", name))?;
+        // The block rate measured when the report was built
+        let block_time_ms = report["clock"]["seconds_per_block"]
+            .as_str()
+            .and_then(|seconds| seconds.parse::<f64>().ok())
+            .map(|seconds| seconds * 1000.0)
+            .or(profile.block_time_ms.map(|ms| ms as f64))
+            .ok_or_else(|| format!("No block time for chain {}", name))?;
+
+        Ok(Chain {
+            name: name.to_string(),
+            symbol,
+            decimals,
+            ss58_prefix,
+            block_time_ms,
+            vote_locking_period: profile.vote_locking_period,
+        })
+    }
+
+    /// Makes this the chain that amounts and addresses are formatted for. A run formats
+    /// for one chain only, so installing a different one fails.
+    pub fn install(self) -> Result<&'static Chain, Box<dyn std::error::Error>> {
+        let installed = CHAIN.get_or_init(|| self.clone());
+        if *installed != self {
+            return Err(format!(
+                "Amounts are already formatted for chain {}, not {}",
+                installed.name, self.name
+            )
+            .into());
+        }
+        Ok(installed)
+    }
+
+    /// Converts plancks to whole tokens.
+    pub fn to_units(&self, plancks: f64) -> f64 {
+        plancks / 10f64.powi(self.decimals as i32)
+    }
+}
+
+/// Formats plancks as an exact decimal amount of tokens, with every decimal place.
+pub fn format_plancks(plancks: u128, decimals: u8) -> String {
+    let digits = format!("{:0>width$}", plancks, width = decimals as usize + 1);
+    let (units, fraction) = digits.split_at(digits.len() - decimals as usize);
+    if fraction.is_empty() {
+        units.to_string()
+    } else {
+        format!("{}.{}", units, fraction)
+    }
+}
+
+/// The chain installed at startup.
+pub fn current() -> &'static Chain {
+    CHAIN
+        .get()
+        .expect("chain parameters are loaded before any account")
+}
+
+/// Reads a numeric runtime constant from the connected chain's metadata, if it has one.
+fn constant(api: &OnlineClient<PolkadotConfig>, pallet: &str, name: &str) -> Option<u128> {
+    let address = subxt::dynamic::constant(pallet, name);
+    api.constants()
+        .at(&address)
+        .ok()?
+        .to_value()
+        .ok()?
+        .as_u128()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_profiles() {
+        let kusama = ChainProfile::builtin("kusama").unwrap();
+        assert_eq!(kusama.symbol.as_deref(), Some("KSM"));
+        assert_eq!(kusama.decimals, Some(12));
+        assert_eq!(kusama.ss58_prefix, Some(2));
+        assert!(ChainProfile::builtin("unknown").is_none());
+    }
+
+    #[test]
+    fn custom_profile_overrides_builtin() {
+        let custom = ChainProfile {
+            url: Some("ws://127.0.0.1:9944".to_string()),
+            block_time_ms: Some(12_000),
+            ..ChainProfile::default()
+        };
+        let profile = custom.or(ChainProfile::builtin("westend").unwrap());
+        assert_eq!(profile.url.as_deref(), Some("ws://127.0.0.1:9944"));
+        assert_eq!(profile.symbol.as_deref(), Some("WND"));
+        assert_eq!(profile.block_time_ms, Some(12_000));
+    }
+
+    #[test]
+    fn plancks_format_exactly() {
+        assert_eq!(format_plancks(12_345_678_901, 10), "1.2345678901");
+        assert_eq!(format_plancks(5, 10), "0.0000000005");
+        assert_eq!(
+            format_plancks(u128::MAX, 12),
+            "340282366920938463463374607.431768211455"
+        );
+        assert_eq!(format_plancks(42, 0), "42");
+    }
+
+    #[test]
+    fn units_follow_decimals() {
+        let chain = Chain {
+            name: "kusama".to_string(),
+            symbol: "KSM".to_string(),
+            decimals: 12,
+            ss58_prefix: 2,
+            block_time_ms: 6000.0,
+            vote_locking_period: None,
+        };
+        assert_eq!(chain.to_units(1.5e12), 1.5);
+    }
+
+    #[test]
+    fn installing_another_chain_fails() {
+        // The only test that installs a chain, since the installed one is global
+        let kusama = Chain {
+            name: "kusama".to_string(),
+            symbol: "KSM".to_string(),
+            decimals: 12,
+            ss58_prefix: 2,
+            block_time_ms: 6000.0,
+            vote_locking_period: None,
+        };
+        assert_eq!(kusama.clone().install().unwrap(), &kusama);
+        assert!(kusama.clone().install().is_ok());
+        let westend = Chain {
+            name: "westend".to_string(),
+            ss58_prefix: 42,
+            ..kusama
+        };
+        assert!(westend.install().is_err());
+    }
+
+    #[test]
+    fn cached_report_names_its_chain() {
+        let report = serde_json::json!({
+            "chain": { "name": "kusama", "symbol": "KSM", "decimals": 12 },
+            "clock": { "block": 100, "seconds_per_block": "6.012" },
+        });
+        let chain = Chain::from_report(&report, ChainProfile::builtin("kusama").unwrap()).unwrap();
+        assert_eq!(chain.symbol, "KSM");
+        assert_eq!(chain.decimals, 12);
+        // Reports cached before the prefix was recorded take it from the profile
+        assert_eq!(chain.ss58_prefix, 2);
+        assert_eq!(chain.block_time_ms, 6012.0);
+
+        assert!(Chain::from_report(&report, ChainProfile::default()).is_err());
+    }
+}
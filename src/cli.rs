@@ -0,0 +1,369 @@
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
+use std::io::{self, IsTerminal, Read};
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::cache::Cache;
+use crate::chain::{ChainProfile, DEFAULT_CHAIN};
+use crate::config::Config;
+use crate::connection::DEFAULT_URL;
+use crate::decode::Decoding;
+use crate::liquidity::DEFAULT_LADDER_DAYS;
+use crate::snapshot::{BlockId, DEFAULT_PARALLELISM};
+
+/// Generate lock, vesting and referenda reports for Polkadot accounts.
+#[derive(Parser, Debug)]
+#[command(name = "locks-report", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Commands,
+
+    /// RPC endpoint to connect to.
+    #[arg(long, env = "LOCKS_REPORT_URL", global = true, value_name = "URL")]
+    pub url: Option<String>,
+
+    /// Endpoint to try, in order, when the primary one fails or drops. Can be repeated.
+    #[arg(
+        long = "fallback-url",
+        env = "LOCKS_REPORT_FALLBACK_URLS",
+        value_delimiter = ',',
+        global = true,
+        value_name = "URL"
+    )]
+    pub fallback_urls: Vec<String>,
+
+    /// Config file. Defaults to `locks-report.toml` in the working directory, if present.
+    #[arg(long, env = "LOCKS_REPORT_CONFIG", global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// How storage is decoded. `auto` uses the bundled metadata and decodes dynamically
+    /// where it doesn't match the chain.
+    #[arg(long, env = "LOCKS_REPORT_DECODING", global = true, value_enum)]
+    pub decoding: Option<Decoding>,
+
+    /// Chain to report on: `polkadot`, `kusama`, `westend`, or a profile from the config
+    /// file.
+    #[arg(long, env = "LOCKS_REPORT_CHAIN", global = true, value_name = "NAME")]
+    pub chain: Option<String>,
+
+    /// Read state as of a past block, given by number or `0x` hash. Needs an archive node.
+    #[arg(long, global = true, value_name = "BLOCK", conflicts_with = "at_date")]
+    pub at_block: Option<BlockId>,
+
+    /// Read state as of the end of a past day (UTC), given as `YYYY-MM-DD`. Needs an
+    /// archive node.
+    #[arg(long, global = true, value_name = "DATE")]
+    pub at_date: Option<NaiveDate>,
+
+    /// Storage queries to have in flight at once. Accounts, tracks and referenda are
+    /// fetched concurrently up to this limit.
+    #[arg(
+        long,
+        env = "LOCKS_REPORT_PARALLELISM",
+        global = true,
+        value_name = "N"
+    )]
+    pub parallelism: Option<usize>,
+
+    /// Where finished referenda and the last report's data are kept between runs.
+    /// Defaults to `$XDG_CACHE_HOME/locks-report`.
+    #[arg(
+        long,
+        env = "LOCKS_REPORT_CACHE_DIR",
+        global = true,
+        value_name = "PATH"
+    )]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Build the report from the data cached by the last online run, without connecting.
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Last day of each liquidity ladder bucket but the last, counted from now, such as
+    /// `90,180,270,365` for quarters. Defaults to `7,14,28,60`.
+    #[arg(
+        long,
+        env = "LOCKS_REPORT_LADDER_DAYS",
+        value_delimiter = ',',
+        global = true,
+        value_name = "DAYS"
+    )]
+    pub ladder_days: Vec<u32>,
+}
+
+impl Cli {
+    /// The primary endpoint followed by its fallbacks. Flags and environment variables
+    /// override the config file, which overrides the chain profile's endpoint and then
+    /// the public Polkadot RPC.
+    pub fn endpoints(&self, config: &Config, profile: &ChainProfile) -> Vec<String> {
+        let primary = self
+            .url
+            .clone()
+            .or_else(|| config.url.clone())
+            .or_else(|| profile.url.clone())
+            .unwrap_or_else(|| DEFAULT_URL.to_string());
+        let fallbacks = if self.fallback_urls.is_empty() {
+            &config.fallback_urls
+        } else {
+            &self.fallback_urls
+        };
+
+        let mut endpoints = vec![primary];
+        for url in fallbacks {
+            if !endpoints.contains(url) {
+                endpoints.push(url.clone());
+            }
+        }
+        endpoints
+    }
+
+    /// The selected chain's name and profile. A profile in the config file takes
+    /// precedence over the built-in one of the same name, and fills its gaps from it.
+    pub fn chain_profile(
+        &self,
+        config: &Config,
+    ) -> Result<(String, ChainProfile), Box<dyn std::error::Error>> {
+        let name = self
+            .chain
+            .clone()
+            .or_else(|| config.chain.clone())
+            .unwrap_or_else(|| DEFAULT_CHAIN.to_string());
+        let builtin = ChainProfile::builtin(&name);
+        let profile = match (config.chains.get(&name).cloned(), builtin) {
+            (Some(custom), Some(builtin)) => custom.or(builtin),
+            (Some(custom), None) => custom,
+            (None, Some(builtin)) => builtin,
+            (None, None) => {
+                return Err(format!(
+                    "Unknown chain {}. Define it under [chains.{}] in the config file.",
+                    name, name
+                )
+                .into())
+            }
+        };
+        Ok((name, profile))
+    }
+
+    /// The parallelism limit from the flag or environment, then the config file.
+    pub fn parallelism(&self, config: &Config) -> usize {
+        self.parallelism
+            .or(config.parallelism)
+            .unwrap_or(DEFAULT_PARALLELISM)
+    }
+
+    /// The ladder's bucket days from the flag or environment, then the config file.
+    pub fn ladder_days(&self, config: &Config) -> Vec<u32> {
+        if !self.ladder_days.is_empty() {
+            return self.ladder_days.clone();
+        }
+        config
+            .ladder_days
+            .clone()
+            .unwrap_or_else(|| DEFAULT_LADDER_DAYS.to_vec())
+    }
+
+    /// The cache in the directory from the flag or environment, then the config file.
+    pub fn cache(&self, config: &Config) -> Cache {
+        let dir = self
+            .cache_dir
+            .clone()
+            .or_else(|| config.cache_dir.clone())
+            .unwrap_or_else(Cache::default_dir);
+        Cache::new(dir)
+    }
+
+    /// The decoding mode from the flag or environment, then the config file.
+    pub fn decoding(&self, config: &Config) -> Decoding {
+        self.decoding.or(config.decoding).unwrap_or_default()
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Build the liquidity matrix report for the given accounts.
+    Report(ReportArgs),
+    /// Print balance locks and conviction voting class locks.
+    Locks(AddressArgs),
+    /// Print vesting schedules.
+    Vesting(AddressArgs),
+    /// Print every referendum the accounts voted on, with vote details.
+    Referenda(AddressArgs),
+}
+
+impl Commands {
+    pub fn address_args(&self) -> &AddressArgs {
+        match self {
+            Commands::Report(args) => &args.addresses,
+            Commands::Locks(args) | Commands::Vesting(args) | Commands::Referenda(args) => args,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    #[command(flatten)]
+    pub addresses: AddressArgs,
+
+    /// Where to write the report. Defaults to a timestamped file in the current directory.
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Don't open the generated report in the default browser.
+    #[arg(long)]
+    pub no_open: bool,
+
+    /// Output format: the HTML liquidity matrix, the full report model as JSON, CSV
+    /// tables for spreadsheets, the liquidity ladder as a terminal or Markdown table, or an
+    /// iCalendar file of unlocks.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Html)]
+    pub format: ReportFormat,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Html,
+    /// Every account's balances, locks, votes, intervals and vesting, described by
+    /// `schema_version`.
+    Json,
+    /// Two tables next to the output path: `_ladder.csv` with every account's liquidity
+    /// ladder and `_intervals.csv` with every locked interval.
+    Csv,
+    /// The liquidity ladder as a coloured table, printed to the terminal unless an output
+    /// path is given.
+    Table,
+    /// The liquidity ladder as a Markdown table, for forum posts and wikis.
+    Markdown,
+    /// One calendar event per unlock, with a reminder the day before.
+    Ics,
+}
+
+impl ReportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Html => "html",
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Table => "txt",
+            ReportFormat::Markdown => "md",
+            ReportFormat::Ics => "ics",
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct AddressArgs {
+    /// SS58 addresses to process. Use `-` to read them from stdin.
+    #[arg(value_name = "ADDRESS")]
+    pub addresses: Vec<String>,
+
+    /// Read addresses from a file, one per line.
+    #[arg(short, long, value_name = "PATH")]
+    pub file: Option<PathBuf>,
+
+    /// Ask for addresses in a macOS dialog.
+    #[arg(short, long, conflicts_with_all = ["addresses", "file"])]
+    pub interactive: bool,
+}
+
+impl AddressArgs {
+    /// Collects addresses from every source that was given. Stdin is read when `-` is passed,
+    /// or when nothing else was given and stdin is not a terminal.
+    pub fn resolve(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if self.interactive {
+            return read_addresses_from_dialog();
+        }
+
+        let mut addresses = Vec::new();
+        let mut read_stdin = false;
+        for address in &self.addresses {
+            if address == "-" {
+                read_stdin = true;
+            } else {
+                addresses.push(address.clone());
+            }
+        }
+        if let Some(path) = &self.file {
+            addresses.extend(read_addresses_from_file(path)?);
+        }
+        if addresses.is_empty() && !io::stdin().is_terminal() {
+            read_stdin = true;
+        }
+        if read_stdin {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            addresses.extend(parse_address_lines(&input));
+        }
+
+        if addresses.is_empty() {
+            return Err(
+                "No addresses given. Pass them as arguments, with --file, or on stdin.".into(),
+            );
+        }
+        Ok(addresses)
+    }
+}
+
+// Split the input by newline, filter out any empty lines and `#` comments
+fn parse_address_lines(input: &str) -> Vec<String> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|s| !s.is_empty() && !s.starts_with('#'))
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn read_addresses_from_file(path: &PathBuf) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(parse_address_lines(&contents))
+}
+
+fn read_addresses_from_dialog() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if !cfg!(target_os = "macos") {
+        return Err("Interactive mode needs osascript and is only available on macOS.".into());
+    }
+
+    let apple_script = r#"
+    set defaultText to "
+
+
+
+
+
+
+
+
+    "
+    set userInput to text returned of (display dialog "Please input addresses, separated by newlines:" default answer defaultText)
+    return userInput
+    "#;
+
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(apple_script)
+        .output()?;
+
+    let user_input = String::from_utf8(output.stdout)?;
+    Ok(parse_address_lines(&user_input))
+}
+
+/// Opens a file with the platform's default handler.
+pub fn open_in_browser(path: &std::path::Path) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let status = command.arg(path).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("opener exited with {}", status)))
+    }
+}
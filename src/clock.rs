@@ -0,0 +1,369 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::dynamic::Value;
+use subxt::{OnlineClient, PolkadotConfig};
+
+use crate::connection;
+use crate::decode::{self, Decoding};
+use crate::polkadot;
+
+/// How far back the block rate is measured, at the chain's nominal block time.
+const RATE_WINDOW_MS: f64 = 7.0 * 24.0 * 3600.0 * 1000.0;
+
+/// Uncertainty of the nominal block time, as a share of it, when the rate can't be
+/// measured.
+const NOMINAL_SPREAD: f64 = 0.05;
+
+/// Time of a block, either read from chain state or estimated from the block rate.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BlockTime {
+    pub date: DateTime<Utc>,
+    /// How far off the date may be, in seconds either way. Zero for on-chain timestamps.
+    pub margin_secs: i64,
+    pub estimated: bool,
+}
+
+/// Converts block numbers to dates. Past blocks are read from `Timestamp::Now` on an
+/// archive node and cached; future blocks are extrapolated from the block rate measured
+/// over the last week.
+pub struct BlockClock {
+    api: OnlineClient<PolkadotConfig>,
+    rpc: LegacyRpcMethods<PolkadotConfig>,
+    decoding: Decoding,
+    current_block: u32,
+    current_time: DateTime<Utc>,
+    ms_per_block: f64,
+    /// Difference between the rates measured over each half of the window, used as the
+    /// uncertainty of `ms_per_block`.
+    ms_per_block_spread: f64,
+    anchors: Mutex<HashMap<u32, DateTime<Utc>>>,
+}
+
+impl BlockClock {
+    /// Anchors the clock at `current_block` and measures the recent block rate. The
+    /// nominal block time is assumed when the chain is too young to measure one, or the
+    /// node has pruned the state of the blocks it is measured over.
+    pub async fn new(
+        api: OnlineClient<PolkadotConfig>,
+        rpc: LegacyRpcMethods<PolkadotConfig>,
+        decoding: Decoding,
+        current_block: u32,
+        nominal_ms_per_block: f64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut clock = BlockClock {
+            api,
+            rpc,
+            decoding,
+            current_block,
+            current_time: Utc::now(),
+            ms_per_block: nominal_ms_per_block,
+            ms_per_block_spread: 0.0,
+            anchors: Mutex::new(HashMap::new()),
+        };
+
+        clock.current_time = clock.timestamp_at(current_block).await?;
+
+        let window_blocks = (RATE_WINDOW_MS / nominal_ms_per_block.max(1.0)) as u32;
+        let window = window_blocks.min(current_block.saturating_sub(1));
+        if window >= 2 {
+            let start = current_block - window;
+            let middle = current_block - window / 2;
+            match clock.window_times(start, middle).await {
+                Ok((start_time, middle_time)) => {
+                    (clock.ms_per_block, clock.ms_per_block_spread) = measure_rate(
+                        (start, start_time),
+                        (middle, middle_time),
+                        (current_block, clock.current_time),
+                    );
+                    println!(
+                        "[Clock] Block {} at {}, {:.3}s per block (±{:.3}s) over the last {} blocks",
+                        current_block,
+                        clock.current_time.format("%Y-%m-%d %H:%M:%S"),
+                        clock.ms_per_block / 1000.0,
+                        clock.ms_per_block_spread / 1000.0,
+                        window
+                    );
+                    return Ok(clock);
+                }
+                Err(e) if connection::is_connection_error(e.as_ref()) => return Err(e),
+                Err(e) => eprintln!(
+                    "[Error] No timestamp {} blocks back, assuming the nominal block time: {}",
+                    window, e
+                ),
+            }
+        }
+
+        clock.ms_per_block_spread = nominal_ms_per_block * NOMINAL_SPREAD;
+        println!(
+            "[Clock] Block {} at {}, {:.3}s per block (±{:.3}s) assumed",
+            current_block,
+            clock.current_time.format("%Y-%m-%d %H:%M:%S"),
+            clock.ms_per_block / 1000.0,
+            clock.ms_per_block_spread / 1000.0,
+        );
+        Ok(clock)
+    }
+
+    /// Times of the first and middle block of the window the rate is measured over.
+    async fn window_times(
+        &self,
+        start: u32,
+        middle: u32,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>), Box<dyn std::error::Error>> {
+        Ok((
+            self.timestamp_at(start).await?,
+            self.timestamp_at(middle).await?,
+        ))
+    }
+
+    /// Points the clock at a new connection after a failover, keeping the cached anchors.
+    pub fn reconnect(
+        &mut self,
+        api: OnlineClient<PolkadotConfig>,
+        rpc: LegacyRpcMethods<PolkadotConfig>,
+    ) {
+        self.api = api;
+        self.rpc = rpc;
+    }
+
+    pub fn current_block(&self) -> u32 {
+        self.current_block
+    }
+
+    pub fn current_time(&self) -> DateTime<Utc> {
+        self.current_time
+    }
+
+    pub fn ms_per_block(&self) -> f64 {
+        self.ms_per_block
+    }
+
+    /// Date of `block`. Blocks up to the current one come from chain state; if the node
+    /// no longer has that state they are extrapolated backwards like future blocks are
+    /// forwards.
+    pub async fn block_time(&self, block: u32) -> Result<BlockTime, Box<dyn std::error::Error>> {
+        if block <= self.current_block {
+            match self.timestamp_at(block).await {
+                Ok(date) => {
+                    return Ok(BlockTime {
+                        date,
+                        margin_secs: 0,
+                        estimated: false,
+                    })
+                }
+                Err(e) if connection::is_connection_error(e.as_ref()) => return Err(e),
+                Err(e) => eprintln!(
+                    "[Error] No timestamp for block {}, estimating: {}",
+                    block, e
+                ),
+            }
+        }
+        Ok(self.estimate(block))
+    }
+
+    /// Extrapolates the date of `block` from the current block at the measured rate.
+    pub fn estimate(&self, block: u32) -> BlockTime {
+        extrapolate(
+            (self.current_block, self.current_time),
+            self.ms_per_block,
+            self.ms_per_block_spread,
+            block,
+        )
+    }
+
+    /// Reads `Timestamp::Now` as of `block`, which is the time the block was authored.
+    async fn timestamp_at(&self, block: u32) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+        if let Some(date) = self.anchors.lock().unwrap().get(&block) {
+            return Ok(*date);
+        }
+
+        let date = timestamp_at(&self.api, &self.rpc, self.decoding, block).await?;
+        self.anchors.lock().unwrap().insert(block, date);
+        Ok(date)
+    }
+}
+
+/// Block rate over the blocks from `start` to `end`, and the difference between the
+/// rates over each half, split at `middle`, as its uncertainty. Both in milliseconds
+/// per block.
+fn measure_rate(
+    start: (u32, DateTime<Utc>),
+    middle: (u32, DateTime<Utc>),
+    end: (u32, DateTime<Utc>),
+) -> (f64, f64) {
+    let rate = |(from_block, from): (u32, DateTime<Utc>), (to_block, to): (u32, DateTime<Utc>)| {
+        (to - from).num_milliseconds() as f64 / (to_block - from_block) as f64
+    };
+    let spread = (rate(start, middle) - rate(middle, end)).abs();
+    (rate(start, end), spread)
+}
+
+/// Date of `block` counted from the `anchor` block at `ms_per_block`, off by up to
+/// `spread` milliseconds for every block in between.
+fn extrapolate(
+    anchor: (u32, DateTime<Utc>),
+    ms_per_block: f64,
+    spread: f64,
+    block: u32,
+) -> BlockTime {
+    let blocks = block as i64 - anchor.0 as i64;
+    let offset = Duration::milliseconds((blocks as f64 * ms_per_block) as i64);
+    let margin = blocks.unsigned_abs() as f64 * spread / 1000.0;
+    BlockTime {
+        date: anchor.1 + offset,
+        margin_secs: margin.ceil() as i64,
+        estimated: true,
+    }
+}
+
+/// Reads `Timestamp::Now` as of `block` from an archive node, with the generated types or
+/// dynamically if they don't match the chain or `decoding` asks for it.
+async fn timestamp_at(
+    api: &OnlineClient<PolkadotConfig>,
+    rpc: &LegacyRpcMethods<PolkadotConfig>,
+    decoding: Decoding,
+    block: u32,
+) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+    let hash = rpc
+        .chain_get_block_hash(Some(block.into()))
+        .await?
+        .ok_or_else(|| format!("Block {} not found", block))?;
+    let storage = api.storage().at(hash);
+    let millis = match decoding.tries_static() {
+        true => match storage.fetch(&polkadot::storage().timestamp().now()).await {
+            Ok(millis) => Some(millis),
+            Err(e) if decoding == Decoding::Auto && decode::is_metadata_mismatch(&e) => {
+                decode::note_fallback("Timestamp::Now", &e);
+                None
+            }
+            Err(e) => return Err(e.into()),
+        },
+        false => None,
+    };
+    let millis = match millis {
+        Some(millis) => millis,
+        None => {
+            let storage_query = subxt::dynamic::storage("Timestamp", "Now", Vec::<Value>::new());
+            match storage.fetch(&storage_query).await? {
+                Some(value) => value
+                    .to_value()?
+                    .as_u128()
+                    .and_then(|millis| u64::try_from(millis).ok()),
+                None => None,
+            }
+        }
+    }
+    .ok_or_else(|| format!("No timestamp stored at block {}", block))?;
+    Utc.timestamp_millis_opt(millis as i64)
+        .single()
+        .ok_or_else(|| format!("Invalid timestamp {} at block {}", millis, block).into())
+}
+
+/// Last block authored before `date`, searching blocks 1 to `latest` by their timestamps.
+/// Returns `None` if the chain had no blocks before then.
+pub async fn last_block_before(
+    api: &OnlineClient<PolkadotConfig>,
+    rpc: &LegacyRpcMethods<PolkadotConfig>,
+    decoding: Decoding,
+    date: DateTime<Utc>,
+    latest: u32,
+) -> Result<Option<u32>, Box<dyn std::error::Error>> {
+    search_last_before(date, latest, |block| {
+        timestamp_at(api, rpc, decoding, block)
+    })
+    .await
+}
+
+/// Binary search behind `last_block_before`, reading block times with `timestamp`.
+async fn search_last_before<F, Fut>(
+    date: DateTime<Utc>,
+    latest: u32,
+    timestamp: F,
+) -> Result<Option<u32>, Box<dyn std::error::Error>>
+where
+    F: Fn(u32) -> Fut,
+    Fut: std::future::Future<Output = Result<DateTime<Utc>, Box<dyn std::error::Error>>>,
+{
+    if latest == 0 || timestamp(1).await? >= date {
+        return Ok(None);
+    }
+
+    // Invariant: block `low` is before `date`, and every block after `high` is not
+    let (mut low, mut high) = (1, latest);
+    while low < high {
+        let middle = low + (high - low).div_ceil(2);
+        if timestamp(middle).await? < date {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    Ok(Some(low))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn steady_blocks_have_no_spread() {
+        let (rate, spread) = measure_rate((0, at(0)), (100, at(600)), (200, at(1200)));
+        assert_eq!(rate, 6000.0);
+        assert_eq!(spread, 0.0);
+    }
+
+    #[test]
+    fn uneven_halves_spread_the_rate() {
+        // 6s blocks, then 7s blocks
+        let (rate, spread) = measure_rate((0, at(0)), (100, at(600)), (200, at(1300)));
+        assert_eq!(rate, 6500.0);
+        assert_eq!(spread, 1000.0);
+    }
+
+    #[test]
+    fn estimates_count_from_the_anchor() {
+        // (block, expected seconds from the anchor's, expected margin)
+        let cases = [
+            (1_000, 0, 0),
+            (1_010, 60, 3),
+            (990, -60, 3),
+            // Partial seconds of margin round up
+            (1_001, 6, 1),
+        ];
+        for (block, offset, margin) in cases {
+            let time = extrapolate((1_000, at(0)), 6000.0, 250.0, block);
+            assert_eq!(time.date, at(offset), "block {}", block);
+            assert_eq!(time.margin_secs, margin, "block {}", block);
+            assert!(time.estimated);
+        }
+    }
+
+    async fn six_second_blocks(block: u32) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+        Ok(at(block as i64 * 6))
+    }
+
+    #[tokio::test]
+    async fn last_block_before_a_date() {
+        // (date, latest, expected)
+        let cases = [
+            (at(60), 100, Some(9)),
+            (at(61), 100, Some(10)),
+            (at(10_000), 100, Some(100)),
+            (at(6), 100, None),
+            (at(60), 0, None),
+        ];
+        for (date, latest, expected) in cases {
+            let block = search_last_before(date, latest, six_second_blocks)
+                .await
+                .unwrap();
+            assert_eq!(block, expected, "{} with {} blocks", date, latest);
+        }
+    }
+}
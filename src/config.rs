@@ -0,0 +1,57 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::chain::ChainProfile;
+use crate::decode::Decoding;
+
+/// Config file looked up in the working directory when `--config` isn't given.
+const DEFAULT_CONFIG_FILE: &str = "locks-report.toml";
+
+/// Settings read from the TOML config file. Every field is optional; command-line
+/// flags and environment variables take precedence over anything set here.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    /// RPC endpoint to connect to.
+    pub url: Option<String>,
+    /// Endpoints tried in order when the primary one fails or drops.
+    pub fallback_urls: Vec<String>,
+    /// How storage is decoded: `auto`, `static` or `dynamic`.
+    pub decoding: Option<Decoding>,
+    /// Storage queries to have in flight at once.
+    pub parallelism: Option<usize>,
+    /// Where data is kept between runs.
+    pub cache_dir: Option<PathBuf>,
+    /// Last day of each liquidity ladder bucket but the last.
+    pub ladder_days: Option<Vec<u32>>,
+    /// Chain to report on: a built-in one or a profile from `chains`.
+    pub chain: Option<String>,
+    /// Custom chain profiles by name. A profile named like a built-in one overrides
+    /// its settings.
+    pub chains: HashMap<String, ChainProfile>,
+}
+
+impl Config {
+    /// Loads the config from `path`, or from `locks-report.toml` if it exists. A missing
+    /// default file is not an error, but a missing explicit one is.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+                if !default.exists() {
+                    return Ok(Config::default());
+                }
+                default
+            }
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        let config = toml::from_str(&contents)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        println!("[Config] Loaded {}", path.display());
+        Ok(config)
+    }
+}
@@ -0,0 +1,110 @@
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::backend::rpc::RpcClient;
+use subxt::{OnlineClient, PolkadotConfig};
+
+pub const DEFAULT_URL: &str = "wss://rpc.polkadot.io:443";
+
+/// A client connected to one of a list of endpoints. The first endpoint is the
+/// preferred one; the rest are fallbacks tried in order.
+pub struct NodeConnection {
+    endpoints: Vec<String>,
+    current: usize,
+    api: OnlineClient<PolkadotConfig>,
+    rpc: LegacyRpcMethods<PolkadotConfig>,
+    rpc_client: RpcClient,
+}
+
+impl NodeConnection {
+    /// Connects to the first endpoint that accepts a connection.
+    pub async fn connect(endpoints: Vec<String>) -> Result<Self, Box<dyn std::error::Error>> {
+        if endpoints.is_empty() {
+            return Err("No RPC endpoints configured.".into());
+        }
+        let (current, api, rpc_client) = connect_from(&endpoints, 0).await?;
+        Ok(NodeConnection {
+            endpoints,
+            current,
+            api,
+            rpc: LegacyRpcMethods::new(rpc_client.clone()),
+            rpc_client,
+        })
+    }
+
+    pub fn api(&self) -> &OnlineClient<PolkadotConfig> {
+        &self.api
+    }
+
+    /// Raw RPC methods, for the calls the client doesn't expose, such as looking up a block
+    /// hash by number.
+    pub fn rpc(&self) -> &LegacyRpcMethods<PolkadotConfig> {
+        &self.rpc
+    }
+
+    /// The underlying RPC client, for building clients that decode with another runtime.
+    pub fn rpc_client(&self) -> &RpcClient {
+        &self.rpc_client
+    }
+
+    pub fn url(&self) -> &str {
+        &self.endpoints[self.current]
+    }
+
+    /// Reconnects after the current endpoint failed, starting with the next one in the
+    /// list and wrapping around so the failed endpoint is retried last.
+    pub async fn failover(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let (current, api, rpc_client) = connect_from(&self.endpoints, self.current + 1).await?;
+        self.current = current;
+        self.api = api;
+        self.rpc = LegacyRpcMethods::new(rpc_client.clone());
+        self.rpc_client = rpc_client;
+        Ok(())
+    }
+
+    pub fn endpoint_count(&self) -> usize {
+        self.endpoints.len()
+    }
+}
+
+async fn connect_from(
+    endpoints: &[String],
+    start: usize,
+) -> Result<(usize, OnlineClient<PolkadotConfig>, RpcClient), Box<dyn std::error::Error>> {
+    let mut last_error = None;
+    for offset in 0..endpoints.len() {
+        let index = (start + offset) % endpoints.len();
+        let url = &endpoints[index];
+        println!("[Connection] Attempting to connect to '{}'...", url);
+        match connect_to(url).await {
+            Ok((api, rpc_client)) => return Ok((index, api, rpc_client)),
+            Err(e) => {
+                eprintln!("[Error] Failed to connect to '{}': {}", url, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(format!(
+        "Could not connect to any endpoint: {}",
+        last_error.map(|e| e.to_string()).unwrap_or_default()
+    )
+    .into())
+}
+
+async fn connect_to(url: &str) -> Result<(OnlineClient<PolkadotConfig>, RpcClient), subxt::Error> {
+    let rpc_client = RpcClient::from_url(url).await?;
+    let api = OnlineClient::<PolkadotConfig>::from_rpc_client(rpc_client.clone()).await?;
+    Ok((api, rpc_client))
+}
+
+/// Whether an error came from the RPC transport, meaning the request may succeed against
+/// another endpoint. Decoding and metadata errors are not retried.
+pub fn is_connection_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    let subxt_error = error.downcast_ref::<subxt::Error>().or_else(|| {
+        error
+            .downcast_ref::<Box<subxt::Error>>()
+            .map(|e| e.as_ref())
+    });
+    matches!(
+        subxt_error,
+        Some(subxt::Error::Rpc(_)) | Some(subxt::Error::Io(_))
+    )
+}
@@ -0,0 +1,70 @@
+/// Number of `VoteLockingPeriod`s a vote stays locked for after the referendum ends,
+/// following `Conviction::lock_periods` in pallet-conviction-voting. Conviction `None`
+/// (0) carries no lock at all; `Locked1x` to `Locked6x` double from one period to 32.
+pub fn lock_periods(conviction: u8) -> u32 {
+    match conviction {
+        0 => 0,
+        1..=6 => 1 << (conviction - 1),
+        _ => panic!("Unknown conviction value: {}", conviction),
+    }
+}
+
+/// Block at which a vote cast with `conviction` can be unlocked, given the block the
+/// lock counts from.
+pub fn unlock_block(from_block: u32, conviction: u8, vote_locking_period: u32) -> u32 {
+    from_block.saturating_add(lock_periods(conviction).saturating_mul(vote_locking_period))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Polkadot's VoteLockingPeriod: 28 days of 6 second blocks
+    const VOTE_LOCKING_PERIOD: u32 = 28 * 24 * 60 * 10;
+
+    #[test]
+    fn lock_periods_follow_the_pallet() {
+        let cases = [(0, 0), (1, 1), (2, 2), (3, 4), (4, 8), (5, 16), (6, 32)];
+        for (conviction, periods) in cases {
+            assert_eq!(
+                lock_periods(conviction),
+                periods,
+                "conviction {}",
+                conviction
+            );
+        }
+    }
+
+    #[test]
+    fn unlock_block_for_every_conviction() {
+        let end = 18_000_000;
+        let cases = [
+            (0, end),
+            (1, end + VOTE_LOCKING_PERIOD),
+            (2, end + 2 * VOTE_LOCKING_PERIOD),
+            (3, end + 4 * VOTE_LOCKING_PERIOD),
+            (4, end + 8 * VOTE_LOCKING_PERIOD),
+            (5, end + 16 * VOTE_LOCKING_PERIOD),
+            (6, end + 32 * VOTE_LOCKING_PERIOD),
+        ];
+        for (conviction, expected) in cases {
+            assert_eq!(
+                unlock_block(end, conviction, VOTE_LOCKING_PERIOD),
+                expected,
+                "conviction {}",
+                conviction
+            );
+        }
+    }
+
+    #[test]
+    fn unlock_block_saturates() {
+        assert_eq!(unlock_block(u32::MAX - 1, 6, VOTE_LOCKING_PERIOD), u32::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown conviction value: 7")]
+    fn unknown_conviction_panics() {
+        lock_periods(7);
+    }
+}
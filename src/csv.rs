@@ -0,0 +1,177 @@
+use handlebars::JsonValue;
+
+use crate::chain::format_plancks;
+
+const LADDER_HEADER: &[&str] = &["account", "bucket", "amount", "symbol", "prior_lock"];
+
+const INTERVALS_HEADER: &[&str] = &[
+    "account",
+    "source",
+    "referendum",
+    "track",
+    "conviction",
+    "amount",
+    "symbol",
+    "start_date",
+    "end_date",
+    "release",
+];
+
+/// The report as two CSV tables, with amounts as exact decimals.
+pub struct Tables {
+    /// One row per account per liquidity ladder bucket.
+    pub ladder: String,
+    /// One row per locked interval.
+    pub intervals: String,
+}
+
+/// Builds the CSV tables from the report model.
+pub fn tables(report: &JsonValue) -> Result<Tables, String> {
+    let decimals = report["chain"]["decimals"]
+        .as_u64()
+        .and_then(|decimals| u8::try_from(decimals).ok())
+        .ok_or("The report has no token decimals; build it again online")?;
+    let symbol = report["chain"]["symbol"].as_str().unwrap_or_default();
+    let amount = |value: &JsonValue| {
+        value
+            .as_str()
+            .and_then(|plancks| plancks.parse().ok())
+            .map(|plancks| format_plancks(plancks, decimals))
+            .ok_or("The report has no exact amounts; build it again online")
+    };
+
+    let mut ladder = vec![row(LADDER_HEADER)];
+    let mut intervals = vec![row(INTERVALS_HEADER)];
+    for account in report["accounts"].as_array().into_iter().flatten() {
+        let address = account["address"].as_str().unwrap_or_default();
+        let data = &account["data"];
+
+        for bucket in data["liquidity"]["locks"].as_array().into_iter().flatten() {
+            ladder.push(row(&[
+                address,
+                bucket["lock_category"].as_str().unwrap_or_default(),
+                &amount(&bucket["plancks"])?,
+                symbol,
+                &bucket["prior_lock"].as_bool().unwrap_or(false).to_string(),
+            ]));
+        }
+
+        for interval in data["intervals"].as_array().into_iter().flatten() {
+            // Standard votes carry their conviction in the vote, delegations directly
+            let conviction = interval["vote"]["conviction"]
+                .as_u64()
+                .or_else(|| interval["conviction"].as_u64());
+            intervals.push(row(&[
+                address,
+                interval["source"].as_str().unwrap_or_default(),
+                &optional(interval["referendum"].as_u64()),
+                &optional(interval["track"].as_u64()),
+                &optional(conviction),
+                &amount(&interval["plancks"])?,
+                symbol,
+                interval["start_date"].as_str().unwrap_or_default(),
+                interval["end_date"].as_str().unwrap_or_default(),
+                interval["release"]["release"].as_str().unwrap_or_default(),
+            ]));
+        }
+    }
+
+    Ok(Tables {
+        ladder: ladder.concat(),
+        intervals: intervals.concat(),
+    })
+}
+
+fn optional(value: Option<u64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// One CSV line, quoting fields as RFC 4180 asks.
+fn row(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    format!("{}\r\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn report() -> JsonValue {
+        json!({
+            "chain": { "symbol": "DOT", "decimals": 10 },
+            "accounts": [{
+                "address": "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5",
+                "data": {
+                    "liquidity": { "locks": [
+                        { "lock_category": "Locked 60+ Days", "plancks": "12345678901", "prior_lock": false },
+                        { "lock_category": "Locked 0 Days", "plancks": "0", "prior_lock": false },
+                    ]},
+                    "intervals": [
+                        {
+                            "source": "vote", "referendum": 120, "track": 33,
+                            "vote": { "type": "standard", "direction": "aye", "conviction": 3 },
+                            "plancks": "50000000000",
+                            "start_date": "2024-01-01T00:00:00Z", "end_date": "2024-03-01T00:00:00Z",
+                            "release": { "release": "at_end" },
+                        },
+                        {
+                            "source": "staking_bonded",
+                            "plancks": "1",
+                            "start_date": "2024-01-01T00:00:00Z", "end_date": "2024-01-29T00:00:00Z",
+                            "release": { "release": "at_end" },
+                        },
+                    ],
+                },
+            }],
+        })
+    }
+
+    #[test]
+    fn tables_have_exact_amounts() {
+        let tables = tables(&report()).unwrap();
+        let ladder: Vec<_> = tables.ladder.lines().collect();
+        assert_eq!(ladder[0], "account,bucket,amount,symbol,prior_lock");
+        assert_eq!(
+            ladder[1],
+            "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5,Locked 60+ Days,1.2345678901,DOT,false"
+        );
+        assert_eq!(ladder.len(), 3);
+
+        let intervals: Vec<_> = tables.intervals.lines().collect();
+        assert_eq!(
+            intervals[1],
+            "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5,vote,120,33,3,5.0000000000,DOT,\
+             2024-01-01T00:00:00Z,2024-03-01T00:00:00Z,at_end"
+        );
+        assert_eq!(
+            intervals[2],
+            "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5,staking_bonded,,,,0.0000000001,DOT,\
+             2024-01-01T00:00:00Z,2024-01-29T00:00:00Z,at_end"
+        );
+    }
+
+    #[test]
+    fn reports_without_exact_amounts_are_rejected() {
+        let mut old = report();
+        old["chain"]["decimals"] = JsonValue::Null;
+        assert!(tables(&old).is_err());
+    }
+
+    #[test]
+    fn fields_are_quoted() {
+        assert_eq!(
+            row(&["a,b", "say \"hi\"", "c"]),
+            "\"a,b\",\"say \"\"hi\"\"\",c\r\n"
+        );
+    }
+}
@@ -0,0 +1,779 @@
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use subxt::dynamic::{At, Value};
+use subxt::error::MetadataError;
+use subxt::ext::scale_value::ValueDef;
+use subxt::utils::AccountId32;
+
+use crate::locks::{Referendum, ReferendumState, TrackTimings};
+use crate::storage::{
+    AccountData, AccountVote, ActiveEra, BalanceLock, PriorLock, StakingLedger, UnlockChunk,
+    VestingInfo, Voting,
+};
+
+/// How storage is decoded.
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Decoding {
+    /// Use the types generated from the bundled metadata, and fall back to dynamic
+    /// decoding for storage items and constants whose types no longer match the chain.
+    #[default]
+    Auto,
+    /// Only use the generated types; fail on a metadata mismatch.
+    Static,
+    /// Decode everything against the connected chain's metadata.
+    Dynamic,
+}
+
+impl Decoding {
+    pub fn tries_static(self) -> bool {
+        self != Decoding::Dynamic
+    }
+}
+
+/// Storage items and constants already reported as falling back, so each is only
+/// reported once.
+static FALLBACKS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Storage items and constants already reported as missing from the chain.
+static MISSING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Whether the static fast path failed because the bundled metadata doesn't match the
+/// chain, rather than because of the connection or a missing entry.
+pub fn is_metadata_mismatch(e: &subxt::Error) -> bool {
+    !is_missing(e)
+        && matches!(
+            e,
+            subxt::Error::Metadata(_) | subxt::Error::Decode(_) | subxt::Error::Codec(_)
+        )
+}
+
+/// Whether the chain's metadata has no such pallet, storage item or constant at all, as
+/// on chains without staking or vesting.
+pub fn is_missing(e: &subxt::Error) -> bool {
+    matches!(
+        e,
+        subxt::Error::Metadata(
+            MetadataError::PalletNameNotFound(_)
+                | MetadataError::StorageNotFoundInPallet(_)
+                | MetadataError::StorageEntryNotFound(_)
+                | MetadataError::ConstantNameNotFound(_)
+        )
+    )
+}
+
+/// Treats a storage item or constant the chain doesn't have as holding no data, and
+/// reports that once per item. Other errors are passed on.
+pub fn missing_as_none<T>(
+    item: &str,
+    result: Result<Option<T>, subxt::Error>,
+) -> Result<Option<T>, Box<subxt::Error>> {
+    match result {
+        Err(e) if is_missing(&e) => {
+            if MISSING.lock().unwrap().insert(item.to_string()) {
+                eprintln!(
+                    "[Decoding] The chain has no {} ({}), leaving it out",
+                    item, e
+                );
+            }
+            Ok(None)
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Reports, once per storage item or constant, that it is being decoded dynamically.
+pub fn note_fallback(item: &str, e: &subxt::Error) {
+    if FALLBACKS.lock().unwrap().insert(item.to_string()) {
+        eprintln!(
+            "[Decoding] {} doesn't match the bundled metadata ({}), decoding it dynamically",
+            item, e
+        );
+    }
+}
+
+fn as_u32<T>(value: Option<&Value<T>>) -> Option<u32> {
+    value
+        .and_then(Value::as_u128)
+        .and_then(|n| u32::try_from(n).ok())
+}
+
+fn as_u128<T>(value: Option<&Value<T>>) -> Option<u128> {
+    value.and_then(Value::as_u128)
+}
+
+fn variant<T>(value: &Value<T>) -> Option<&str> {
+    match &value.value {
+        ValueDef::Variant(variant) => Some(&variant.name),
+        _ => None,
+    }
+}
+
+/// Reads a dynamically decoded `ReferendumInfo`. Only the fields the report uses are
+/// looked at, so changes to the rest of the type, such as new origins, don't matter.
+pub fn referendum<T>(value: &Value<T>) -> Result<Referendum, Box<dyn std::error::Error>> {
+    let name = variant(value).ok_or("ReferendumInfo is not an enum")?;
+    let end = || as_u32(value.at(0)).ok_or_else(|| format!("{} referendum has no end block", name));
+
+    let state = match name {
+        "Ongoing" => {
+            let status = value.at(0);
+            let deciding = status.at("deciding");
+            let deciding_since = match deciding.map(variant) {
+                Some(Some("Some")) => as_u32(deciding.at(0).at("since")),
+                _ => None,
+            };
+            let tally = status.at("tally");
+            let ayes = tally.at("ayes").and_then(Value::as_u128);
+            let nays = tally.at("nays").and_then(Value::as_u128);
+            return Ok(Referendum {
+                state: ReferendumState::Ongoing {
+                    track: as_u32(status.at("track"))
+                        .and_then(|track| u16::try_from(track).ok())
+                        .ok_or("Ongoing referendum has no track")?,
+                    submitted: as_u32(status.at("submitted"))
+                        .ok_or("Ongoing referendum has no submission block")?,
+                    deciding_since,
+                },
+                tally: ayes.zip(nays),
+            });
+        }
+        "Approved" => ReferendumState::Approved { end: end()? },
+        "Rejected" => ReferendumState::Rejected { end: end()? },
+        "Cancelled" => ReferendumState::Cancelled { end: end()? },
+        "TimedOut" => ReferendumState::TimedOut { end: end()? },
+        "Killed" => ReferendumState::Killed { end: end()? },
+        other => return Err(format!("Unknown referendum state {}", other).into()),
+    };
+    Ok(Referendum { state, tally: None })
+}
+
+/// Entries of a dynamically decoded `BoundedVec` or `WeakBoundedVec`, which wraps its
+/// `Vec` of entries in a composite of one field.
+fn bounded_items<T>(value: &Value<T>) -> impl Iterator<Item = &Value<T>> {
+    let entries = match value.at(0).map(|inner| &inner.value) {
+        Some(ValueDef::Composite(entries)) => Some(entries.values()),
+        _ => None,
+    };
+    entries.into_iter().flatten()
+}
+
+/// Reads the entries of dynamically decoded `Balances::Holds` or `Balances::Freezes` as
+/// the pallet, its reason and the amount in plancks. Reasons are runtime enums with one
+/// variant per pallet, each wrapping that pallet's own reason enum.
+pub fn reasons<T>(value: &Value<T>) -> Vec<(String, String, u128)> {
+    bounded_items(value)
+        .filter_map(|entry| {
+            let id = entry.at("id")?;
+            let amount = entry.at("amount")?.as_u128()?;
+            let pallet = variant(id).unwrap_or("Unknown").to_string();
+            let reason = id
+                .at(0)
+                .and_then(variant)
+                .map_or_else(|| pallet.clone(), str::to_string);
+            Some((pallet, reason, amount))
+        })
+        .collect()
+}
+
+/// Bytes of a dynamically decoded byte array, such as a lock ID, or of a newtype around
+/// one, such as `AccountId32`.
+fn bytes<T>(value: &Value<T>) -> Option<Vec<u8>> {
+    let ValueDef::Composite(fields) = &value.value else {
+        return None;
+    };
+    let fields: Vec<_> = fields.values().collect();
+    match fields.as_slice() {
+        [inner] if matches!(inner.value, ValueDef::Composite(_)) => bytes(inner),
+        _ => fields
+            .iter()
+            .map(|field| field.as_u128().and_then(|n| u8::try_from(n).ok()))
+            .collect(),
+    }
+}
+
+/// Reads a dynamically decoded `AccountId32`.
+pub fn account_id<T>(value: &Value<T>) -> Option<AccountId32> {
+    let bytes: [u8; 32] = bytes(value)?.try_into().ok()?;
+    Some(AccountId32(bytes))
+}
+
+/// Reads the balance fields of a dynamically decoded `System::Account` entry.
+pub fn account_data<T>(value: &Value<T>) -> Result<AccountData, Box<dyn std::error::Error>> {
+    let data = value.at("data").ok_or("AccountInfo has no data")?;
+    let field = |name| as_u128(data.at(name));
+    let frozen = field("frozen").or_else(|| Some(field("misc_frozen")?.max(field("fee_frozen")?)));
+    Ok(AccountData {
+        free: field("free").ok_or("AccountData has no free balance")?,
+        reserved: field("reserved").ok_or("AccountData has no reserved balance")?,
+        frozen: frozen.ok_or("AccountData has no frozen balance")?,
+    })
+}
+
+/// Reads the entries of a dynamically decoded `Balances::Locks`.
+pub fn balance_locks<T>(value: &Value<T>) -> Vec<BalanceLock> {
+    bounded_items(value)
+        .filter_map(|lock| {
+            Some(BalanceLock {
+                id: bytes(lock.at("id")?)?.try_into().ok()?,
+                amount: as_u128(lock.at("amount"))?,
+            })
+        })
+        .collect()
+}
+
+/// Reads the track and amount of each entry of a dynamically decoded
+/// `ConvictionVoting::ClassLocksFor`.
+pub fn class_locks<T>(value: &Value<T>) -> Vec<(u16, u128)> {
+    bounded_items(value)
+        .filter_map(|lock| {
+            let track = u16::try_from(as_u32(lock.at(0))?).ok()?;
+            Some((track, as_u128(lock.at(1))?))
+        })
+        .collect()
+}
+
+fn prior_lock<T>(value: Option<&Value<T>>) -> PriorLock {
+    PriorLock {
+        block: as_u32(value.at(0)).unwrap_or(0),
+        balance: as_u128(value.at(1)).unwrap_or(0),
+    }
+}
+
+fn account_vote<T>(value: &Value<T>) -> Option<AccountVote> {
+    let field = |name| as_u128(value.at(name));
+    match variant(value)? {
+        "Standard" => {
+            // `Vote` is a newtype around its encoded byte
+            let vote = value.at("vote")?;
+            let vote = vote.as_u128().or_else(|| vote.at(0)?.as_u128())?;
+            Some(AccountVote::standard(
+                u8::try_from(vote).ok()?,
+                field("balance")?,
+            ))
+        }
+        "Split" => Some(AccountVote::Split {
+            aye: field("aye")?,
+            nay: field("nay")?,
+        }),
+        "SplitAbstain" => Some(AccountVote::SplitAbstain {
+            aye: field("aye")?,
+            nay: field("nay")?,
+            abstain: field("abstain")?,
+        }),
+        _ => None,
+    }
+}
+
+/// Conviction from its variant name, `None` or `Locked1x` to `Locked6x`.
+fn conviction(name: &str) -> Option<u8> {
+    match name {
+        "None" => Some(0),
+        _ => name.strip_prefix("Locked")?.strip_suffix('x')?.parse().ok(),
+    }
+}
+
+/// Reads a dynamically decoded `ConvictionVoting::VotingFor` entry. A vote in a shape the
+/// report doesn't know fails the whole entry, since leaving it out would understate the
+/// account's locks.
+pub fn voting<T>(value: &Value<T>) -> Result<Voting, Box<dyn std::error::Error>> {
+    let fields = value.at(0);
+    match variant(value).ok_or("Voting is not an enum")? {
+        "Casting" => Ok(Voting::Casting {
+            votes: bounded_items(fields.at("votes").ok_or("Casting has no votes")?)
+                .map(|vote| {
+                    let ref_num = as_u32(vote.at(0)).ok_or("Casting vote has no referendum")?;
+                    let account_vote = vote.at(1).and_then(account_vote).ok_or_else(|| {
+                        format!("Vote on referendum {} has an unknown shape", ref_num)
+                    })?;
+                    Ok((ref_num, account_vote))
+                })
+                .collect::<Result<_, Box<dyn std::error::Error>>>()?,
+            prior: prior_lock(fields.at("prior")),
+        }),
+        "Delegating" => Ok(Voting::Delegating {
+            balance: as_u128(fields.at("balance")).ok_or("Delegating has no balance")?,
+            target: fields
+                .at("target")
+                .and_then(account_id)
+                .ok_or("Delegating has no target")?,
+            conviction: fields
+                .at("conviction")
+                .and_then(variant)
+                .and_then(conviction)
+                .ok_or("Delegating has no conviction")?,
+            prior: prior_lock(fields.at("prior")),
+        }),
+        other => Err(format!("Unknown voting kind {}", other).into()),
+    }
+}
+
+/// Reads a dynamically decoded `Staking::Ledger` entry. Unlocking chunks it can't read
+/// fail the entry, like unknown votes do.
+pub fn staking_ledger<T>(value: &Value<T>) -> Result<StakingLedger, Box<dyn std::error::Error>> {
+    let unlocking = value
+        .at("unlocking")
+        .ok_or("StakingLedger has no unlocking")?;
+    Ok(StakingLedger {
+        total: as_u128(value.at("total")).ok_or("StakingLedger has no total")?,
+        active: as_u128(value.at("active")).ok_or("StakingLedger has no active stake")?,
+        unlocking: unlocking_chunks(unlocking).ok_or("Unlocking chunk has an unknown shape")?,
+    })
+}
+
+fn unlocking_chunks<T>(value: &Value<T>) -> Option<Vec<UnlockChunk>> {
+    bounded_items(value)
+        .map(|chunk| {
+            Some(UnlockChunk {
+                value: as_u128(chunk.at("value"))?,
+                era: as_u32(chunk.at("era"))?,
+            })
+        })
+        .collect()
+}
+
+/// Reads a dynamically decoded `Staking::ActiveEra`.
+pub fn active_era<T>(value: &Value<T>) -> Result<ActiveEra, Box<dyn std::error::Error>> {
+    let start = value.at("start");
+    Ok(ActiveEra {
+        index: as_u32(value.at("index")).ok_or("ActiveEraInfo has no index")?,
+        start: match start.map(variant) {
+            Some(Some("Some")) => {
+                as_u128(start.at(0)).and_then(|millis| u64::try_from(millis).ok())
+            }
+            _ => None,
+        },
+    })
+}
+
+/// Reads the schedules of a dynamically decoded `Vesting::Vesting` entry.
+pub fn vesting<T>(value: &Value<T>) -> Vec<VestingInfo> {
+    bounded_items(value)
+        .filter_map(|schedule| {
+            Some(VestingInfo {
+                locked: as_u128(schedule.at("locked"))?,
+                per_block: as_u128(schedule.at("per_block"))?,
+                starting_block: as_u32(schedule.at("starting_block"))?,
+            })
+        })
+        .collect()
+}
+
+/// Reads the periods of each track in a dynamically decoded `Referenda::Tracks` constant.
+pub fn tracks<T>(value: &Value<T>) -> Vec<(u16, TrackTimings)> {
+    let ValueDef::Composite(tracks) = &value.value else {
+        return Vec::new();
+    };
+    tracks
+        .values()
+        .filter_map(|track| {
+            let id = u16::try_from(as_u32(track.at(0))?).ok()?;
+            let info = track.at(1)?;
+            let timings = TrackTimings {
+                prepare_period: as_u32(info.at("prepare_period"))?,
+                decision_period: as_u32(info.at("decision_period"))?,
+                confirm_period: as_u32(info.at("confirm_period"))?,
+            };
+            Some((id, timings))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `BoundedVec` as dynamic decoding shapes it.
+    fn bounded_vec<const N: usize>(entries: [Value; N]) -> Value {
+        Value::unnamed_composite([Value::unnamed_composite(entries)])
+    }
+
+    #[test]
+    fn missing_items_read_as_empty_but_other_errors_pass() {
+        let missing =
+            subxt::Error::Metadata(MetadataError::StorageEntryNotFound("Freezes".to_string()));
+        assert!(!is_metadata_mismatch(&missing));
+        assert!(matches!(
+            missing_as_none::<u128>("Balances::Freezes", Err(missing)),
+            Ok(None)
+        ));
+
+        let mismatch = subxt::Error::Metadata(MetadataError::IncompatibleCodegen);
+        assert!(is_metadata_mismatch(&mismatch));
+        assert!(missing_as_none::<u128>("Balances::Locks", Err(mismatch)).is_err());
+        assert!(matches!(
+            missing_as_none("Balances::Locks", Ok(Some(5))),
+            Ok(Some(5))
+        ));
+    }
+
+    #[test]
+    fn ongoing_referendum_with_deciding_and_tally() {
+        let value = Value::unnamed_variant(
+            "Ongoing",
+            [Value::named_composite([
+                ("track", Value::u128(33)),
+                ("origin", Value::unnamed_variant("NewOrigin", [])),
+                ("submitted", Value::u128(100)),
+                (
+                    "deciding",
+                    Value::unnamed_variant(
+                        "Some",
+                        [Value::named_composite([
+                            ("since", Value::u128(150)),
+                            ("confirming", Value::unnamed_variant("None", [])),
+                        ])],
+                    ),
+                ),
+                (
+                    "tally",
+                    Value::named_composite([
+                        ("ayes", Value::u128(7)),
+                        ("nays", Value::u128(3)),
+                        ("support", Value::u128(5)),
+                    ]),
+                ),
+            ])],
+        );
+        let referendum = referendum(&value).unwrap();
+        assert_eq!(
+            referendum.state,
+            ReferendumState::Ongoing {
+                track: 33,
+                submitted: 100,
+                deciding_since: Some(150),
+            }
+        );
+        assert_eq!(referendum.tally, Some((7, 3)));
+    }
+
+    #[test]
+    fn finished_referenda_read_their_end_block() {
+        let deposit = Value::unnamed_variant("None", []);
+        let approved =
+            Value::unnamed_variant("Approved", [Value::u128(500), deposit.clone(), deposit]);
+        assert_eq!(
+            referendum(&approved).unwrap().state,
+            ReferendumState::Approved { end: 500 }
+        );
+
+        let killed = Value::unnamed_variant("Killed", [Value::u128(42)]);
+        assert_eq!(
+            referendum(&killed).unwrap().state,
+            ReferendumState::Killed { end: 42 }
+        );
+    }
+
+    #[test]
+    fn hold_reasons_name_pallet_and_reason() {
+        let holds = bounded_vec([Value::named_composite([
+            (
+                "id",
+                Value::unnamed_variant("Preimage", [Value::unnamed_variant("Preimage", [])]),
+            ),
+            ("amount", Value::u128(1_000)),
+        ])]);
+        assert_eq!(
+            reasons(&holds),
+            [("Preimage".to_string(), "Preimage".to_string(), 1_000)]
+        );
+    }
+
+    #[test]
+    fn freeze_reasons_name_pallet_and_reason() {
+        let entry = |pallet: &str, reason: &str, amount: u128| {
+            Value::named_composite([
+                (
+                    "id",
+                    Value::unnamed_variant(pallet, [Value::unnamed_variant(reason, [])]),
+                ),
+                ("amount", Value::u128(amount)),
+            ])
+        };
+        let freezes = bounded_vec([
+            entry("NominationPools", "PoolMinBalance", 5_000),
+            entry("Staking", "Staking", 7),
+        ]);
+        assert_eq!(
+            reasons(&freezes),
+            [
+                (
+                    "NominationPools".to_string(),
+                    "PoolMinBalance".to_string(),
+                    5_000
+                ),
+                ("Staking".to_string(), "Staking".to_string(), 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn account_data_reads_frozen_or_the_older_split_fields() {
+        let account =
+            |data: Value| Value::named_composite([("nonce", Value::u128(1)), ("data", data)]);
+        let current = account(Value::named_composite([
+            ("free", Value::u128(100)),
+            ("reserved", Value::u128(10)),
+            ("frozen", Value::u128(40)),
+            ("flags", Value::u128(0)),
+        ]));
+        let legacy = account(Value::named_composite([
+            ("free", Value::u128(100)),
+            ("reserved", Value::u128(10)),
+            ("misc_frozen", Value::u128(40)),
+            ("fee_frozen", Value::u128(25)),
+        ]));
+        let expected = AccountData {
+            free: 100,
+            reserved: 10,
+            frozen: 40,
+        };
+        assert_eq!(account_data(&current).unwrap(), expected);
+        assert_eq!(account_data(&legacy).unwrap(), expected);
+    }
+
+    #[test]
+    fn balance_locks_read_id_and_amount() {
+        let id = Value::unnamed_composite(b"staking ".map(|byte| Value::u128(byte as u128)));
+        let locks = bounded_vec([Value::named_composite([
+            ("id", id),
+            ("amount", Value::u128(500)),
+            ("reasons", Value::unnamed_variant("All", [])),
+        ])]);
+        assert_eq!(
+            balance_locks(&locks),
+            [BalanceLock {
+                id: *b"staking ",
+                amount: 500,
+            }]
+        );
+    }
+
+    #[test]
+    fn class_locks_read_track_and_amount() {
+        let locks = bounded_vec([
+            Value::unnamed_composite([Value::u128(0), Value::u128(70)]),
+            Value::unnamed_composite([Value::u128(33), Value::u128(5)]),
+        ]);
+        assert_eq!(class_locks(&locks), [(0, 70), (33, 5)]);
+    }
+
+    #[test]
+    fn casting_reads_each_kind_of_vote_and_the_prior_lock() {
+        let vote =
+            |ref_num: u128, vote: Value| Value::unnamed_composite([Value::u128(ref_num), vote]);
+        let value = Value::unnamed_variant(
+            "Casting",
+            [Value::named_composite([
+                (
+                    "votes",
+                    bounded_vec([
+                        vote(
+                            5,
+                            Value::named_variant(
+                                "Standard",
+                                [
+                                    ("vote", Value::unnamed_composite([Value::u128(131)])),
+                                    ("balance", Value::u128(9)),
+                                ],
+                            ),
+                        ),
+                        vote(
+                            6,
+                            Value::named_variant(
+                                "SplitAbstain",
+                                [
+                                    ("aye", Value::u128(1)),
+                                    ("nay", Value::u128(2)),
+                                    ("abstain", Value::u128(3)),
+                                ],
+                            ),
+                        ),
+                    ]),
+                ),
+                (
+                    "delegations",
+                    Value::named_composite([
+                        ("votes", Value::u128(0)),
+                        ("capital", Value::u128(0)),
+                    ]),
+                ),
+                (
+                    "prior",
+                    Value::unnamed_composite([Value::u128(1_200), Value::u128(40)]),
+                ),
+            ])],
+        );
+        assert_eq!(
+            voting(&value).unwrap(),
+            Voting::Casting {
+                votes: vec![
+                    (
+                        5,
+                        AccountVote::Standard {
+                            aye: true,
+                            conviction: 3,
+                            balance: 9,
+                        }
+                    ),
+                    (
+                        6,
+                        AccountVote::SplitAbstain {
+                            aye: 1,
+                            nay: 2,
+                            abstain: 3,
+                        }
+                    ),
+                ],
+                prior: PriorLock {
+                    block: 1_200,
+                    balance: 40,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_votes_fail_instead_of_being_left_out() {
+        let value = Value::unnamed_variant(
+            "Casting",
+            [Value::named_composite([
+                (
+                    "votes",
+                    bounded_vec([Value::unnamed_composite([
+                        Value::u128(5),
+                        Value::named_variant("Weighted", [("balance", Value::u128(9))]),
+                    ])]),
+                ),
+                (
+                    "prior",
+                    Value::unnamed_composite([Value::u128(0), Value::u128(0)]),
+                ),
+            ])],
+        );
+        let error = voting(&value).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Vote on referendum 5 has an unknown shape"
+        );
+    }
+
+    #[test]
+    fn delegating_reads_target_and_conviction() {
+        let target = Value::unnamed_composite([Value::unnamed_composite(
+            [7u8; 32].map(|byte| Value::u128(byte as u128)),
+        )]);
+        let value = Value::unnamed_variant(
+            "Delegating",
+            [Value::named_composite([
+                ("balance", Value::u128(1_000)),
+                ("target", target),
+                ("conviction", Value::unnamed_variant("Locked2x", [])),
+                (
+                    "delegations",
+                    Value::named_composite([
+                        ("votes", Value::u128(0)),
+                        ("capital", Value::u128(0)),
+                    ]),
+                ),
+                (
+                    "prior",
+                    Value::unnamed_composite([Value::u128(0), Value::u128(0)]),
+                ),
+            ])],
+        );
+        assert_eq!(
+            voting(&value).unwrap(),
+            Voting::Delegating {
+                balance: 1_000,
+                target: AccountId32([7; 32]),
+                conviction: 2,
+                prior: PriorLock::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn staking_ledger_and_active_era() {
+        let ledger = Value::named_composite([
+            ("stash", Value::unnamed_composite([])),
+            ("total", Value::u128(300)),
+            ("active", Value::u128(200)),
+            (
+                "unlocking",
+                bounded_vec([Value::named_composite([
+                    ("value", Value::u128(100)),
+                    ("era", Value::u128(1_400)),
+                ])]),
+            ),
+            ("claimed_rewards", bounded_vec([])),
+        ]);
+        assert_eq!(
+            staking_ledger(&ledger).unwrap(),
+            StakingLedger {
+                total: 300,
+                active: 200,
+                unlocking: vec![UnlockChunk {
+                    value: 100,
+                    era: 1_400,
+                }],
+            }
+        );
+
+        let era = Value::named_composite([
+            ("index", Value::u128(1_372)),
+            ("start", Value::unnamed_variant("Some", [Value::u128(99)])),
+        ]);
+        assert_eq!(
+            active_era(&era).unwrap(),
+            ActiveEra {
+                index: 1_372,
+                start: Some(99),
+            }
+        );
+    }
+
+    #[test]
+    fn vesting_schedules() {
+        let schedules = bounded_vec([Value::named_composite([
+            ("locked", Value::u128(1_000)),
+            ("per_block", Value::u128(10)),
+            ("starting_block", Value::u128(50)),
+        ])]);
+        assert_eq!(
+            vesting(&schedules),
+            [VestingInfo {
+                locked: 1_000,
+                per_block: 10,
+                starting_block: 50,
+            }]
+        );
+    }
+
+    #[test]
+    fn track_periods() {
+        let tracks_value = Value::unnamed_composite([Value::unnamed_composite([
+            Value::u128(33),
+            Value::named_composite([
+                ("name", Value::string("medium_spender")),
+                ("max_deciding", Value::u128(50)),
+                ("prepare_period", Value::u128(1_200)),
+                ("decision_period", Value::u128(403_200)),
+                ("confirm_period", Value::u128(57_600)),
+                ("min_enactment_period", Value::u128(14_400)),
+            ]),
+        ])]);
+        assert_eq!(
+            tracks(&tracks_value),
+            [(
+                33,
+                TrackTimings {
+                    prepare_period: 1_200,
+                    decision_period: 403_200,
+                    confirm_period: 57_600,
+                }
+            )]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use chrono::{DateTime, Duration, Utc};
+use handlebars::JsonValue;
+use std::collections::HashSet;
+
+use crate::chain::format_plancks;
+
+/// How long before an unlock the calendar reminds about it.
+const REMINDER: &str = "-P1D";
+
+/// Builds an iCalendar file with one event per locked interval of every account,
+/// at the interval's end date. Intervals that ended by `now` are left out, since there is
+/// nothing left to remind about.
+pub fn calendar(report: &JsonValue, now: DateTime<Utc>) -> Result<String, String> {
+    let chain = report["chain"]["name"].as_str().unwrap_or("polkadot");
+    let symbol = report["chain"]["symbol"].as_str().unwrap_or_default();
+    let decimals = report["chain"]["decimals"]
+        .as_u64()
+        .and_then(|decimals| u8::try_from(decimals).ok());
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//locks-report//Unlock calendar//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{} unlocks", escape(symbol)),
+    ];
+    let mut uids = HashSet::new();
+    for account in report["accounts"].as_array().into_iter().flatten() {
+        let address = account["address"].as_str().unwrap_or_default();
+        for interval in account["data"]["intervals"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let end = interval["end_date"]
+                .as_str()
+                .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                .ok_or_else(|| format!("Interval of {} has no end date", address))?
+                .with_timezone(&Utc);
+            if end <= now {
+                continue;
+            }
+            let amount = match (interval["plancks"].as_str(), decimals) {
+                (Some(plancks), Some(decimals)) => plancks
+                    .parse()
+                    .map(|plancks| format_plancks(plancks, decimals))
+                    .map_err(|e| format!("Invalid amount {}: {}", plancks, e))?,
+                _ => format!("{:.10}", interval["amount"].as_f64().unwrap_or_default()),
+            };
+            let source = describe_source(interval);
+
+            // The same lock keeps its UID from run to run, even as its estimated end
+            // moves, so importing a newer calendar updates the event
+            let key = format!("{}-{}-{}", chain, address, source_key(interval));
+            let mut uid = format!("{}@locks-report", key);
+            let mut duplicate = 1;
+            while !uids.insert(uid.clone()) {
+                duplicate += 1;
+                uid = format!("{}-{}@locks-report", key, duplicate);
+            }
+
+            let mut description = vec![
+                format!("Account: {}", address),
+                format!("Amount: {} {}", amount, symbol),
+                format!("Source: {}", source),
+            ];
+            if let Some(state) = interval["status"]["state"].as_str() {
+                description.push(format!("Status: {}", state.replace('_', " ")));
+            }
+            if let Some(margin) = interval["end_date_margin_secs"].as_i64().filter(|m| *m > 0) {
+                let hours = (margin + 3599) / 3600;
+                description.push(format!("Estimated to within {} hours", hours));
+            }
+
+            lines.extend([
+                "BEGIN:VEVENT".to_string(),
+                format!("UID:{}", uid),
+                format!("DTSTAMP:{}", timestamp(now)),
+                format!("DTSTART:{}", timestamp(end)),
+                format!("DTEND:{}", timestamp(end + Duration::hours(1))),
+                format!(
+                    "SUMMARY:{}",
+                    escape(&format!(
+                        "Unlock {} {} for {}: {}",
+                        amount,
+                        symbol,
+                        short_address(address),
+                        source
+                    ))
+                ),
+                format!("DESCRIPTION:{}", escape(&description.join("\n"))),
+                "BEGIN:VALARM".to_string(),
+                "ACTION:DISPLAY".to_string(),
+                format!("TRIGGER:{}", REMINDER),
+                format!(
+                    "DESCRIPTION:{}",
+                    escape(&format!("{} {} unlocks tomorrow", amount, symbol))
+                ),
+                "END:VALARM".to_string(),
+                "END:VEVENT".to_string(),
+            ]);
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    Ok(lines.iter().map(|line| fold(line)).collect())
+}
+
+/// What identifies a lock between runs, from the fields of its `LockSource`.
+fn source_key(interval: &JsonValue) -> String {
+    let source = interval["source"].as_str().unwrap_or("lock");
+    let id = match source {
+        "vote" => &interval["referendum"],
+        "delegation" | "prior_lock" => &interval["track"],
+        "staking_unbonding" => &interval["era"],
+        "vesting" => &interval["starting_block"],
+        _ => &JsonValue::Null,
+    };
+    match id {
+        JsonValue::Null => source.replace('_', "-"),
+        id => format!("{}-{}", source.replace('_', "-"), id),
+    }
+}
+
+fn describe_source(interval: &JsonValue) -> String {
+    let field = |name: &str| interval[name].to_string();
+    match interval["source"].as_str().unwrap_or_default() {
+        "vote" => match interval["vote"]["conviction"].as_u64() {
+            Some(conviction) => format!(
+                "vote on referendum #{} ({}x conviction)",
+                field("referendum"),
+                conviction
+            ),
+            None => format!("split vote on referendum #{}", field("referendum")),
+        },
+        "delegation" => format!(
+            "delegation on track {} ({}x conviction)",
+            field("track"),
+            field("conviction")
+        ),
+        "prior_lock" => format!("prior lock on track {}", field("track")),
+        "staking_bonded" => "bonded stake, if unbonded now".to_string(),
+        "staking_unbonding" => format!("staking chunk unbonding in era {}", field("era")),
+        "vesting" => format!("vesting schedule from block {}", field("starting_block")),
+        other => other.replace('_', " "),
+    }
+}
+
+fn short_address(address: &str) -> String {
+    match (
+        address.get(..6),
+        address.get(address.len().saturating_sub(6)..),
+    ) {
+        (Some(start), Some(end)) if address.len() > 12 => format!("{}…{}", start, end),
+        _ => address.to_string(),
+    }
+}
+
+fn timestamp(date: DateTime<Utc>) -> String {
+    date.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes text values as RFC 5545 asks.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line into lines of at most 75 octets, without splitting characters.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn report(end_date: &str) -> JsonValue {
+        json!({
+            "chain": { "name": "polkadot", "symbol": "DOT", "decimals": 10 },
+            "accounts": [{
+                "address": "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5",
+                "data": { "intervals": [{
+                    "source": "vote", "referendum": 120, "track": 33,
+                    "vote": { "type": "standard", "direction": "aye", "conviction": 3 },
+                    "plancks": "50000000000",
+                    "status": { "state": "locked_until", "block": 1000 },
+                    "end_date_margin_secs": 0,
+                    "start_date": "2024-01-01T00:00:00Z",
+                    "end_date": end_date,
+                }]},
+            }],
+        })
+    }
+
+    fn unfold(calendar: &str) -> String {
+        calendar.replace("\r\n ", "")
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn one_event_per_interval() {
+        let calendar = unfold(&calendar(&report("2024-03-01T12:00:00Z"), now()).unwrap());
+        assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 1);
+        assert!(calendar.contains("DTSTART:20240301T120000Z\r\n"));
+        assert!(calendar.contains(
+            "SUMMARY:Unlock 5.0000000000 DOT for 15oF4u…Hr6Sp5: vote on referendum #120 (3x conviction)"
+        ));
+        assert!(calendar.contains("TRIGGER:-P1D\r\n"));
+    }
+
+    #[test]
+    fn uids_survive_a_moved_end_date() {
+        let uid = |calendar: String| {
+            unfold(&calendar)
+                .lines()
+                .find(|line| line.starts_with("UID:"))
+                .map(str::to_string)
+        };
+        let first = uid(calendar(&report("2024-03-01T12:00:00Z"), now()).unwrap());
+        let second = uid(calendar(&report("2024-03-02T08:00:00Z"), now()).unwrap());
+        assert_eq!(
+            first.as_deref(),
+            Some("UID:polkadot-15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5-vote-120@locks-report")
+        );
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn unlockable_intervals_get_no_event() {
+        // Already unlockable, its end is the snapshot's time
+        let calendar = calendar(&report("2023-12-31T23:59:00Z"), now()).unwrap();
+        assert!(!calendar.contains("BEGIN:VEVENT"));
+        assert!(!calendar.contains("BEGIN:VALARM"));
+    }
+
+    #[test]
+    fn long_lines_fold_at_75_octets() {
+        let folded = fold(&"x".repeat(100));
+        let lines: Vec<_> = folded.split("\r\n").collect();
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1], format!(" {}", "x".repeat(25)));
+    }
+}
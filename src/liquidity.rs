@@ -0,0 +1,453 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::locks::{LockSource, LockedInterval, Release};
+
+/// Points sampled along a linear release, so the curve follows it closely.
+const LINEAR_SAMPLES: i32 = 24;
+
+/// Last day of each ladder bucket but the last, counted in whole days from now.
+pub const DEFAULT_LADDER_DAYS: [u32; 4] = [7, 14, 28, 60];
+
+/// Longest bucket boundary, a century: past any lock, and well within the dates chrono
+/// can count to.
+const MAX_LADDER_DAY: u32 = 36_500;
+
+/// Bucket colours from the shortest lock to the longest. Ladders with another number of
+/// buckets blend between them.
+const BUCKET_COLOURS: [(u8, u8, u8); 6] = [
+    (0xFF, 0xEB, 0xEE),
+    (0xFF, 0xD2, 0xC1),
+    (0xFF, 0xAC, 0x75),
+    (0xFF, 0x8A, 0x65),
+    (0xFF, 0x70, 0x43),
+    (0xF4, 0x43, 0x36),
+];
+
+/// One bucket of the liquidity ladder.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bucket {
+    /// Name of the bucket in the report model, like `Locked 1-7 Days`.
+    pub label: String,
+    /// Column header in the reports.
+    pub header: String,
+    /// CSS class of its cells.
+    pub class: String,
+    pub colour: String,
+    /// First whole day, counted from now, that is past the bucket. `None` for the last.
+    #[serde(skip)]
+    pub until_day: Option<u32>,
+}
+
+/// Builds the ladder from the last day of each bucket. A bucket for balance that is free
+/// to unlock within a day comes first, and one for everything past the last day last.
+pub fn ladder(days: &[u32]) -> Result<Vec<Bucket>, String> {
+    if days.is_empty() || days[0] == 0 || days.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(format!(
+            "Ladder days must be increasing and above 0, got {:?}",
+            days
+        ));
+    }
+    if let Some(day) = days.iter().find(|&&day| day > MAX_LADDER_DAY) {
+        return Err(format!(
+            "Ladder days must be at most {}, got {}",
+            MAX_LADDER_DAY, day
+        ));
+    }
+
+    let mut buckets = vec![(
+        "Locked 0 Days".to_string(),
+        "Ready to Unlock".to_string(),
+        "locked-0-days".to_string(),
+        Some(1),
+    )];
+    let mut first = 1;
+    for &last in days {
+        let range = if first == last {
+            format!("{}", last)
+        } else {
+            format!("{}-{}", first, last)
+        };
+        let unit = if last == 1 { "Day" } else { "Days" };
+        buckets.push((
+            format!("Locked {} {}", range, unit),
+            format!("Locked {} {}", range, unit),
+            format!("locked-{}-{}", range, unit.to_lowercase()),
+            Some(last + 1),
+        ));
+        first = last + 1;
+    }
+    let over = first - 1;
+    buckets.push((
+        format!("Locked {}+ Days", over),
+        format!("Locked {}+ Days", over),
+        format!("locked-{}-plus-days", over),
+        None,
+    ));
+
+    let count = buckets.len();
+    Ok(buckets
+        .into_iter()
+        .enumerate()
+        .map(|(i, (label, header, class, until_day))| Bucket {
+            label,
+            header,
+            class,
+            colour: colour(i as f64 / (count - 1) as f64),
+            until_day,
+        })
+        .collect())
+}
+
+/// The colour at `position`, from 0 for the first bucket to 1 for the last.
+fn colour(position: f64) -> String {
+    let scaled = position * (BUCKET_COLOURS.len() - 1) as f64;
+    let low = (scaled.floor() as usize).min(BUCKET_COLOURS.len() - 2);
+    let t = scaled - low as f64;
+    let (from, to) = (BUCKET_COLOURS[low], BUCKET_COLOURS[low + 1]);
+    let blend = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    format!(
+        "#{:02X}{:02X}{:02X}",
+        blend(from.0, to.0),
+        blend(from.1, to.1),
+        blend(from.2, to.2)
+    )
+}
+
+/// The locked balance from `date` until the next point.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LockedPoint {
+    pub date: DateTime<Utc>,
+    pub amount: f64,
+}
+
+/// Combines what each interval locks into the account's locked balance. Intervals of one
+/// lock add up or overlap as `LockSource::lock` says, and different locks overlap, so
+/// the frozen balance is the largest of them rather than their sum.
+fn combine<T>(intervals: &[LockedInterval], locked: impl Fn(&LockedInterval) -> T) -> T
+where
+    T: Copy + Default + PartialOrd + std::ops::Add<Output = T>,
+{
+    let mut locks: BTreeMap<&[u8; 8], T> = BTreeMap::new();
+    for interval in intervals {
+        let (id, adds_up) = interval.source.lock();
+        let remaining = locked(interval);
+        let lock = locks.entry(id).or_default();
+        if adds_up {
+            *lock = *lock + remaining;
+        } else if remaining > *lock {
+            *lock = remaining;
+        }
+    }
+    locks.into_values().fold(
+        T::default(),
+        |max, lock| if lock > max { lock } else { max },
+    )
+}
+
+/// Balance the intervals keep locked at `date`.
+pub fn locked_at(intervals: &[LockedInterval], date: DateTime<Utc>) -> f64 {
+    combine(intervals, |interval| interval.remaining_at(date))
+}
+
+/// Plancks the intervals keep locked at `date`.
+pub fn locked_plancks_at(intervals: &[LockedInterval], date: DateTime<Utc>) -> u128 {
+    combine(intervals, |interval| interval.remaining_plancks_at(date))
+}
+
+/// Plancks locked at `now` before anything is unlocked. Intervals that have already ended
+/// still hold their amount until the account removes the vote or unlocks.
+pub fn held_plancks(intervals: &[LockedInterval], now: DateTime<Utc>) -> u128 {
+    combine(intervals, |interval| {
+        if interval.end_date <= now {
+            interval.plancks
+        } else {
+            interval.remaining_plancks_at(now)
+        }
+    })
+}
+
+/// Plancks that become liquid in each window between `now` and the `boundaries`, which
+/// must be in order: first what is held but free to unlock before the first boundary,
+/// then what is released between consecutive boundaries, and last what stays locked
+/// past the final one. The windows add up to everything held, since a lock only frees
+/// the part of the balance no other lock still holds.
+pub fn released_between(
+    intervals: &[LockedInterval],
+    now: DateTime<Utc>,
+    boundaries: &[DateTime<Utc>],
+) -> Vec<u128> {
+    let mut locked = vec![held_plancks(intervals, now)];
+    locked.extend(
+        boundaries
+            .iter()
+            .map(|boundary| locked_plancks_at(intervals, *boundary)),
+    );
+    locked.push(0);
+    locked
+        .windows(2)
+        .map(|window| window[0].saturating_sub(window[1]))
+        .collect()
+}
+
+/// For each window of `released_between`, whether some of what it releases was held by
+/// a prior lock: without prior locks, the window would release less. A prior lock
+/// ending under a larger lock frees nothing, so its window isn't marked.
+pub fn released_by_prior_locks(
+    intervals: &[LockedInterval],
+    now: DateTime<Utc>,
+    boundaries: &[DateTime<Utc>],
+) -> Vec<bool> {
+    let others: Vec<LockedInterval> = intervals
+        .iter()
+        .filter(|interval| !matches!(interval.source, LockSource::PriorLock { .. }))
+        .cloned()
+        .collect();
+    released_between(intervals, now, boundaries)
+        .into_iter()
+        .zip(released_between(&others, now, boundaries))
+        .map(|(all, without)| all > without)
+        .collect()
+}
+
+/// The locked balance as a step function of time from `now`: each point holds until the
+/// next one, and the last point is when everything is unlocked.
+pub fn locked_over_time(intervals: &[LockedInterval], now: DateTime<Utc>) -> Vec<LockedPoint> {
+    let mut dates = vec![now];
+    for interval in intervals {
+        dates.push(interval.end_date);
+        if let Release::Linear { from } = interval.release {
+            let from = from.max(now);
+            let step = (interval.end_date - from) / LINEAR_SAMPLES;
+            dates.extend((0..LINEAR_SAMPLES).map(|i| from + step * i));
+        }
+    }
+    dates.retain(|date| *date >= now);
+    dates.sort();
+    dates.dedup();
+
+    let mut points: Vec<LockedPoint> = Vec::new();
+    for date in dates {
+        let amount = locked_at(intervals, date);
+        if points.last().is_none_or(|last| last.amount != amount) {
+            points.push(LockedPoint { date, amount });
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locks::{LockSource, LockStatus};
+    use chrono::{Duration, TimeZone};
+
+    fn day(n: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::days(n)
+    }
+
+    fn interval(source: LockSource, amount: f64, end: i64) -> LockedInterval {
+        LockedInterval {
+            start_date: day(0),
+            end_date: day(end),
+            end_date_margin_secs: 0,
+            release: Release::AtEnd,
+            amount,
+            plancks: (amount * 1e10) as u128,
+            status: LockStatus::LockedUntil { block: 0 },
+            source,
+        }
+    }
+
+    fn prior(track: u16, amount: f64, end: i64) -> LockedInterval {
+        interval(LockSource::PriorLock { track }, amount, end)
+    }
+
+    fn unbonding(era: u32, amount: f64, end: i64) -> LockedInterval {
+        interval(LockSource::StakingUnbonding { era }, amount, end)
+    }
+
+    #[test]
+    fn default_ladder() {
+        let buckets = ladder(&DEFAULT_LADDER_DAYS).unwrap();
+        let labels: Vec<_> = buckets.iter().map(|bucket| bucket.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "Locked 0 Days",
+                "Locked 1-7 Days",
+                "Locked 8-14 Days",
+                "Locked 15-28 Days",
+                "Locked 29-60 Days",
+                "Locked 60+ Days",
+            ]
+        );
+        assert_eq!(buckets[0].header, "Ready to Unlock");
+        assert_eq!(buckets[5].class, "locked-60-plus-days");
+        // Six buckets use the report's colours as they are
+        assert_eq!(buckets[0].colour, "#FFEBEE");
+        assert_eq!(buckets[2].colour, "#FFAC75");
+        assert_eq!(buckets[5].colour, "#F44336");
+    }
+
+    #[test]
+    fn quarterly_ladder() {
+        let buckets = ladder(&[90, 180, 270, 365]).unwrap();
+        assert_eq!(buckets.len(), 6);
+        assert_eq!(buckets[1].label, "Locked 1-90 Days");
+        assert_eq!(buckets[5].label, "Locked 365+ Days");
+        let until: Vec<_> = buckets.iter().map(|bucket| bucket.until_day).collect();
+        assert_eq!(
+            until,
+            [Some(1), Some(91), Some(181), Some(271), Some(366), None]
+        );
+    }
+
+    #[test]
+    fn ladder_days_must_increase() {
+        assert!(ladder(&[30, 30]).is_err());
+        assert!(ladder(&[0, 7]).is_err());
+        let single = ladder(&[1]).unwrap();
+        assert_eq!(single[1].label, "Locked 1 Day");
+        assert_eq!(single[1].class, "locked-1-day");
+        assert_eq!(single[2].label, "Locked 1+ Days");
+        assert!(ladder(&[]).is_err());
+        // Boundaries past a century would overflow the dates they are counted to
+        assert!(ladder(&[7, 36_500]).is_ok());
+        assert_eq!(
+            ladder(&[7, 4_000_000_000]).unwrap_err(),
+            "Ladder days must be at most 36500, got 4000000000"
+        );
+    }
+
+    #[test]
+    fn votes_overlap() {
+        let intervals = [prior(0, 10.0, 5), prior(1, 30.0, 20), prior(2, 20.0, 40)];
+        assert_eq!(locked_at(&intervals, day(1)), 30.0);
+        assert_eq!(locked_at(&intervals, day(20)), 20.0);
+        assert_eq!(locked_at(&intervals, day(40)), 0.0);
+    }
+
+    #[test]
+    fn stake_adds_up_and_overlaps_votes() {
+        let intervals = [
+            unbonding(1, 15.0, 10),
+            unbonding(2, 15.0, 30),
+            prior(0, 20.0, 20),
+        ];
+        assert_eq!(locked_at(&intervals, day(1)), 30.0);
+        assert_eq!(locked_at(&intervals, day(10)), 20.0);
+        assert_eq!(locked_at(&intervals, day(20)), 15.0);
+    }
+
+    #[test]
+    fn linear_release() {
+        let mut vesting = interval(LockSource::Vesting { starting_block: 0 }, 100.0, 20);
+        vesting.release = Release::Linear { from: day(10) };
+        assert_eq!(locked_at(&[vesting.clone()], day(5)), 100.0);
+        assert_eq!(locked_at(&[vesting.clone()], day(15)), 50.0);
+        assert_eq!(locked_at(&[vesting], day(20)), 0.0);
+    }
+
+    #[test]
+    fn ended_intervals_are_held_until_unlocked() {
+        let intervals = [prior(0, 10.0, 5), prior(1, 4.0, 20)];
+        assert_eq!(held_plancks(&intervals, day(10)), 100_000_000_000);
+        assert_eq!(locked_plancks_at(&intervals, day(10)), 40_000_000_000);
+    }
+
+    #[test]
+    fn buckets_show_newly_liquid_balance() {
+        // The 30 locked until day 20 hides the 10 ending on day 5, which frees nothing
+        let intervals = [prior(0, 10.0, 5), prior(1, 30.0, 20), unbonding(1, 5.0, 30)];
+        let released = released_between(&intervals, day(0), &[day(1), day(10), day(25)]);
+        assert_eq!(
+            released,
+            [0, 0, 250_000_000_000, 50_000_000_000],
+            "now, days 1-10, days 10-25, after day 25"
+        );
+    }
+
+    #[test]
+    fn buckets_add_up_to_the_held_balance() {
+        let mut vesting = interval(LockSource::Vesting { starting_block: 0 }, 100.0, 40);
+        vesting.release = Release::Linear { from: day(0) };
+        let intervals = [
+            prior(0, 50.0, -3),
+            prior(1, 20.0, 12),
+            unbonding(1, 30.0, 8),
+            unbonding(2, 30.0, 16),
+            vesting,
+        ];
+        let boundaries = [day(1), day(8), day(15), day(29), day(61)];
+        let released = released_between(&intervals, day(0), &boundaries);
+        assert_eq!(released.len(), 6);
+        assert_eq!(
+            released.iter().sum::<u128>(),
+            held_plancks(&intervals, day(0))
+        );
+        // The vesting lock is the largest, releasing 100 linearly over 40 days
+        assert_eq!(held_plancks(&intervals, day(0)), 1_000_000_000_000);
+        assert_eq!(released[0], 25_000_000_000);
+    }
+
+    #[test]
+    fn split_locks_across_pallets_overlap() {
+        // Staking adds up to 60, a larger vote of 80 covers it until day 10
+        let intervals = [
+            unbonding(1, 30.0, 5),
+            unbonding(2, 30.0, 15),
+            prior(0, 80.0, 10),
+        ];
+        let released = released_between(&intervals, day(0), &[day(7), day(12)]);
+        assert_eq!(released, [0, 500_000_000_000, 300_000_000_000]);
+    }
+
+    #[test]
+    fn curve_steps_down_at_each_unlock() {
+        let intervals = [prior(0, 10.0, 5), prior(1, 30.0, 20), unbonding(1, 5.0, 30)];
+        let points: Vec<_> = locked_over_time(&intervals, day(0))
+            .into_iter()
+            .map(|point| (point.date, point.amount))
+            .collect();
+        assert_eq!(points, [(day(0), 30.0), (day(20), 5.0), (day(30), 0.0)]);
+    }
+
+    #[test]
+    fn curve_starts_now() {
+        let points = locked_over_time(&[prior(0, 10.0, 5)], day(10));
+        assert_eq!(
+            points,
+            [LockedPoint {
+                date: day(10),
+                amount: 0.0
+            }]
+        );
+    }
+
+    #[test]
+    fn prior_locks_marked_only_where_they_free_balance() {
+        let delegation = |amount, end| {
+            let source = LockSource::Delegation {
+                track: 0,
+                target: String::new(),
+                conviction: 1,
+            };
+            interval(source, amount, end)
+        };
+        let boundaries = [day(7), day(14), day(28)];
+        // Ends under a larger delegation: the delegation frees everything
+        let hidden = [prior(0, 10.0, 5), delegation(30.0, 20)];
+        assert_eq!(
+            released_by_prior_locks(&hidden, day(0), &boundaries),
+            [false; 4]
+        );
+        // Outlasts a smaller delegation: only its own end frees balance
+        let outlasting = [prior(0, 30.0, 20), delegation(10.0, 5)];
+        assert_eq!(
+            released_by_prior_locks(&outlasting, day(0), &boundaries),
+            [false, false, true, false]
+        );
+    }
+}
@@ -0,0 +1,626 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::conviction;
+
+/// A balance that stays locked from `start_date` until `end_date`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedInterval {
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    /// Uncertainty of `end_date` in seconds, when it was estimated from the block rate.
+    pub end_date_margin_secs: i64,
+    pub release: Release,
+    pub amount: f64,
+    /// `amount` exactly, in plancks. Written as a string, since JSON numbers can't hold
+    /// every `u128`.
+    #[serde(
+        serialize_with = "serialize_plancks",
+        deserialize_with = "deserialize_plancks"
+    )]
+    pub plancks: u128,
+    pub status: LockStatus,
+    #[serde(flatten)]
+    pub source: LockSource,
+}
+
+impl LockedInterval {
+    /// Moves an interval counted from the snapshot to start at `now`. The earliest unlock
+    /// of bonded stake or a delegation assumes unbonding or undelegating right away, so it
+    /// stays as far off as it was; locks with an end fixed on chain don't move.
+    pub fn recount_from(&mut self, now: DateTime<Utc>, ms_per_block: f64) {
+        let (LockStatus::Delegated { earliest_unlock } | LockStatus::Bonded { earliest_unlock }) =
+            &mut self.status
+        else {
+            return;
+        };
+        let shift = now - self.start_date;
+        if shift <= chrono::Duration::zero() {
+            return;
+        }
+        let blocks = shift.num_milliseconds() as f64 / ms_per_block;
+        *earliest_unlock = earliest_unlock.saturating_add(blocks as u32);
+        if let Release::Linear { from } = &mut self.release {
+            *from += shift;
+        }
+        self.start_date = now;
+        self.end_date += shift;
+    }
+
+    /// Share of the amount still locked at `date`, as a numerator and denominator.
+    /// Nothing is left from `end_date` on.
+    fn locked_share(&self, date: DateTime<Utc>) -> (i64, i64) {
+        if date >= self.end_date {
+            return (0, 1);
+        }
+        match self.release {
+            Release::AtEnd => (1, 1),
+            Release::Linear { from } if date <= from => (1, 1),
+            Release::Linear { from } => (
+                (self.end_date - date).num_seconds(),
+                (self.end_date - from).num_seconds().max(1),
+            ),
+        }
+    }
+
+    /// Amount still locked at `date`.
+    pub fn remaining_at(&self, date: DateTime<Utc>) -> f64 {
+        let (left, total) = self.locked_share(date);
+        self.amount * left as f64 / total as f64
+    }
+
+    /// Plancks still locked at `date`, rounded down.
+    pub fn remaining_plancks_at(&self, date: DateTime<Utc>) -> u128 {
+        let (left, total) = self.locked_share(date);
+        self.plancks.saturating_mul(left as u128) / total as u128
+    }
+}
+
+fn serialize_plancks<S: serde::Serializer>(
+    plancks: &u128,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(plancks)
+}
+
+fn deserialize_plancks<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u128, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+/// How the locked amount is released.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "release", rename_all = "snake_case")]
+pub enum Release {
+    /// All of it at `end_date`.
+    AtEnd,
+    /// Linearly from `from` until none is left at `end_date`.
+    Linear { from: DateTime<Utc> },
+}
+
+/// What put the lock in place.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum LockSource {
+    Vote {
+        referendum: u32,
+        track: u16,
+        referendum_state: String,
+        vote: VoteKind,
+    },
+    Delegation {
+        track: u16,
+        target: String,
+        conviction: u8,
+    },
+    /// A lock left behind by votes that were removed or a delegation that ended, which
+    /// stays in place until its block.
+    PriorLock { track: u16 },
+    /// Stake that is bonded and not unbonding.
+    StakingBonded,
+    /// Stake unbonding until the given era.
+    StakingUnbonding { era: u32 },
+    /// A vesting schedule starting at the given block.
+    Vesting { starting_block: u32 },
+}
+
+impl LockSource {
+    /// The balance lock this source is part of, and whether sources of the same lock add
+    /// up. Votes and delegations only lock their largest amount, per track and across
+    /// tracks, while bonded and unbonding stake or several vesting schedules add up.
+    pub fn lock(&self) -> (&'static [u8; 8], bool) {
+        match self {
+            LockSource::Vote { .. }
+            | LockSource::Delegation { .. }
+            | LockSource::PriorLock { .. } => (b"pyconvot", false),
+            LockSource::StakingBonded | LockSource::StakingUnbonding { .. } => (b"staking ", true),
+            LockSource::Vesting { .. } => (b"vesting ", true),
+        }
+    }
+}
+
+/// The vote behind a vote lock, with amounts in the chain's token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VoteKind {
+    Standard { direction: String, conviction: u8 },
+    Split { aye: f64, nay: f64 },
+    SplitAbstain { aye: f64, nay: f64, abstain: f64 },
+}
+
+/// The parts of a referendum's on-chain status that decide how long its votes stay locked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ReferendumState {
+    Ongoing {
+        track: u16,
+        submitted: u32,
+        /// Block the decision period started at, if the referendum is deciding.
+        deciding_since: Option<u32>,
+    },
+    Approved {
+        end: u32,
+    },
+    Rejected {
+        end: u32,
+    },
+    Cancelled {
+        end: u32,
+    },
+    TimedOut {
+        end: u32,
+    },
+    Killed {
+        end: u32,
+    },
+}
+
+impl ReferendumState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReferendumState::Ongoing { .. } => "ongoing",
+            ReferendumState::Approved { .. } => "approved",
+            ReferendumState::Rejected { .. } => "rejected",
+            ReferendumState::Cancelled { .. } => "cancelled",
+            ReferendumState::TimedOut { .. } => "timed_out",
+            ReferendumState::Killed { .. } => "killed",
+        }
+    }
+
+    /// Block the referendum finished at. Finished referenda never change again.
+    pub fn end(&self) -> Option<u32> {
+        match self {
+            ReferendumState::Ongoing { .. } => None,
+            ReferendumState::Approved { end }
+            | ReferendumState::Rejected { end }
+            | ReferendumState::Cancelled { end }
+            | ReferendumState::TimedOut { end }
+            | ReferendumState::Killed { end } => Some(*end),
+        }
+    }
+}
+
+/// A referendum as read from chain state, however it was decoded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Referendum {
+    pub state: ReferendumState,
+    /// Ayes and nays in plancks, while the referendum is ongoing.
+    pub tally: Option<(u128, u128)>,
+}
+
+/// Track parameters needed to bound when an ongoing referendum can end at the latest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackTimings {
+    pub prepare_period: u32,
+    pub decision_period: u32,
+    pub confirm_period: u32,
+}
+
+impl ReferendumState {
+    /// Status of a lock that lasts until an ongoing referendum ends, with `unlock` turning
+    /// the referendum's end into the unlock block, or `None` once it has ended. Without
+    /// track timings the referendum is taken to end now.
+    fn pending_lock(
+        &self,
+        current_block: u32,
+        timings: Option<&TrackTimings>,
+        unlock: impl Fn(u32) -> u32,
+    ) -> Option<LockStatus> {
+        match self {
+            ReferendumState::Ongoing {
+                deciding_since: Some(since),
+                ..
+            } => {
+                let end = timings.map_or(current_block, |t| t.worst_case_end(*since));
+                Some(LockStatus::PendingOutcome {
+                    worst_case_unlock: unlock(end),
+                })
+            }
+            ReferendumState::Ongoing { submitted, .. } => {
+                let end = timings.map_or(current_block, |t| {
+                    t.estimated_end(*submitted, current_block)
+                });
+                Some(LockStatus::AwaitingDecision {
+                    estimated_unlock: unlock(end),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl TrackTimings {
+    /// Latest block a deciding referendum can end at: a confirmation started on the last
+    /// block of its decision period runs for one more confirm period.
+    pub fn worst_case_end(&self, deciding_since: u32) -> u32 {
+        deciding_since
+            .saturating_add(self.decision_period)
+            .saturating_add(self.confirm_period)
+    }
+
+    /// End of a referendum that isn't deciding yet, if deciding starts once preparation is
+    /// over, or now if that is overdue. Referenda queued for a deciding slot don't time
+    /// out, so there is no latest end to give.
+    pub fn estimated_end(&self, submitted: u32, current_block: u32) -> u32 {
+        let since = submitted
+            .saturating_add(self.prepare_period)
+            .max(current_block);
+        self.worst_case_end(since)
+    }
+}
+
+/// What a vote's lock amounts to right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum LockStatus {
+    /// Nothing holds the balance any more; removing the vote and unlocking frees it.
+    Unlockable,
+    /// Locked until the given block.
+    LockedUntil { block: u32 },
+    /// The referendum hasn't ended. The unlock block assumes the vote ends up on the
+    /// winning side and the referendum runs as long as its track allows.
+    PendingOutcome { worst_case_unlock: u32 },
+    /// The referendum is preparing or queued for a deciding slot, which has no time limit.
+    /// The unlock block is an estimate that has the referendum start deciding as soon as it
+    /// can and run as long as its track allows.
+    AwaitingDecision { estimated_unlock: u32 },
+    /// Delegated votes stay locked for as long as the delegation lasts. The unlock block is
+    /// the earliest possible one, if the account undelegated now.
+    Delegated { earliest_unlock: u32 },
+    /// Bonded stake stays locked until it is unbonded and the bonding duration has passed.
+    /// The unlock block is the earliest possible one, if it was unbonded now.
+    Bonded { earliest_unlock: u32 },
+}
+
+impl LockStatus {
+    /// Block the balance is expected to become free at, or `None` if it is free already.
+    pub fn unlock_block(&self) -> Option<u32> {
+        match self {
+            LockStatus::Unlockable => None,
+            LockStatus::LockedUntil { block } => Some(*block),
+            LockStatus::PendingOutcome { worst_case_unlock } => Some(*worst_case_unlock),
+            LockStatus::AwaitingDecision { estimated_unlock } => Some(*estimated_unlock),
+            LockStatus::Delegated { earliest_unlock } | LockStatus::Bonded { earliest_unlock } => {
+                Some(*earliest_unlock)
+            }
+        }
+    }
+
+    fn locked_until(block: u32, current_block: u32) -> Self {
+        if block > current_block {
+            LockStatus::LockedUntil { block }
+        } else {
+            LockStatus::Unlockable
+        }
+    }
+}
+
+/// Lock left by a standard vote, following `remove_vote` in pallet-conviction-voting: only
+/// votes on the winning side of an approved or rejected referendum keep a conviction lock,
+/// counted from the block the referendum ended. Cancelled, timed out and killed referenda
+/// leave no lock.
+pub fn standard_vote_lock(
+    state: &ReferendumState,
+    aye: bool,
+    conviction: u8,
+    vote_locking_period: u32,
+    current_block: u32,
+    timings: Option<&TrackTimings>,
+) -> LockStatus {
+    match state {
+        ReferendumState::Ongoing { .. } => state
+            .pending_lock(current_block, timings, |end| {
+                conviction::unlock_block(end, conviction, vote_locking_period)
+            })
+            .unwrap_or(LockStatus::Unlockable),
+        ReferendumState::Approved { end } | ReferendumState::Rejected { end } => {
+            let approved = matches!(state, ReferendumState::Approved { .. });
+            if aye == approved {
+                let unlock = conviction::unlock_block(*end, conviction, vote_locking_period);
+                LockStatus::locked_until(unlock, current_block)
+            } else {
+                LockStatus::Unlockable
+            }
+        }
+        ReferendumState::Cancelled { .. }
+        | ReferendumState::TimedOut { .. }
+        | ReferendumState::Killed { .. } => LockStatus::Unlockable,
+    }
+}
+
+/// Lock left by a split or split-abstain vote. These carry no conviction, so the balance is
+/// only locked while the referendum is ongoing, whichever way it goes.
+pub fn split_vote_lock(
+    state: &ReferendumState,
+    current_block: u32,
+    timings: Option<&TrackTimings>,
+) -> LockStatus {
+    state
+        .pending_lock(current_block, timings, |end| end)
+        .unwrap_or(LockStatus::Unlockable)
+}
+
+/// Balance a split or split-abstain vote locks: all of its parts, since none of them
+/// carries conviction to scale it.
+pub fn split_vote_balance(aye: u128, nay: u128, abstain: u128) -> u128 {
+    aye.saturating_add(nay).saturating_add(abstain)
+}
+
+/// Lock held by a delegation. Undelegating starts the conviction's lock periods from the
+/// undelegate block, so the earliest unlock is one full conviction period from now.
+pub fn delegation_lock(conviction: u8, vote_locking_period: u32, current_block: u32) -> LockStatus {
+    LockStatus::Delegated {
+        earliest_unlock: conviction::unlock_block(current_block, conviction, vote_locking_period),
+    }
+}
+
+/// Status of a `PriorLock`, which binds its balance until its block regardless of votes.
+pub fn prior_lock(block: u32, current_block: u32) -> LockStatus {
+    LockStatus::locked_until(block, current_block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOTE_LOCKING_PERIOD: u32 = 100_800;
+    const END: u32 = 1_000_000;
+    const TIMINGS: TrackTimings = TrackTimings {
+        prepare_period: 100,
+        decision_period: 1_000,
+        confirm_period: 10,
+    };
+
+    #[test]
+    fn only_earliest_unlocks_move_with_now() {
+        let snapshot = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let interval = |status| LockedInterval {
+            start_date: snapshot,
+            end_date: snapshot + chrono::Duration::days(28),
+            end_date_margin_secs: 0,
+            release: Release::AtEnd,
+            amount: 1.0,
+            plancks: 1,
+            status,
+            source: LockSource::StakingBonded,
+        };
+        let now = snapshot + chrono::Duration::days(7);
+
+        let mut bonded = interval(LockStatus::Bonded {
+            earliest_unlock: 1_000,
+        });
+        bonded.recount_from(now, 6_000.0);
+        assert_eq!(bonded.start_date, now);
+        assert_eq!(bonded.end_date, now + chrono::Duration::days(28));
+        assert_eq!(
+            bonded.status,
+            LockStatus::Bonded {
+                earliest_unlock: 1_000 + 7 * 14_400
+            }
+        );
+
+        let mut fixed = interval(LockStatus::LockedUntil { block: 1_000 });
+        fixed.recount_from(now, 6_000.0);
+        assert_eq!(fixed.end_date, snapshot + chrono::Duration::days(28));
+    }
+
+    fn ongoing(submitted: u32, deciding_since: Option<u32>) -> ReferendumState {
+        ReferendumState::Ongoing {
+            track: 0,
+            submitted,
+            deciding_since,
+        }
+    }
+
+    #[test]
+    fn standard_votes_on_finished_referenda() {
+        let locked = |periods: u32| LockStatus::LockedUntil {
+            block: END + periods * VOTE_LOCKING_PERIOD,
+        };
+        let free = LockStatus::Unlockable;
+        let approved = ReferendumState::Approved { end: END };
+        let rejected = ReferendumState::Rejected { end: END };
+        // (state, aye, conviction, expected)
+        let cases = [
+            (&approved, true, 1, locked(1)),
+            (&approved, true, 3, locked(4)),
+            (&approved, false, 3, free),
+            (&rejected, false, 6, locked(32)),
+            (&rejected, true, 6, free),
+            (&approved, true, 0, free),
+            (&ReferendumState::Cancelled { end: END }, true, 6, free),
+            (&ReferendumState::TimedOut { end: END }, false, 6, free),
+            (&ReferendumState::Killed { end: END }, true, 6, free),
+        ];
+        for (state, aye, conviction, expected) in cases {
+            assert_eq!(
+                standard_vote_lock(state, aye, conviction, VOTE_LOCKING_PERIOD, END + 1, None),
+                expected,
+                "{:?}, aye {}, conviction {}",
+                state,
+                aye,
+                conviction
+            );
+        }
+    }
+
+    #[test]
+    fn winning_lock_ends_once_its_periods_pass() {
+        let state = ReferendumState::Approved { end: END };
+        let now = END + VOTE_LOCKING_PERIOD;
+        assert_eq!(
+            standard_vote_lock(&state, true, 1, VOTE_LOCKING_PERIOD, now, None),
+            LockStatus::Unlockable
+        );
+    }
+
+    #[test]
+    fn ends_of_ongoing_referenda() {
+        let worst_case = |block| LockStatus::PendingOutcome {
+            worst_case_unlock: block,
+        };
+        let estimate = |block| LockStatus::AwaitingDecision {
+            estimated_unlock: block,
+        };
+        // (submitted, deciding_since, current_block, expected)
+        let cases = [
+            // Deciding: decision and confirm periods from when deciding started
+            (0, Some(500), 600, worst_case(500 + 1_000 + 10)),
+            // Preparing: deciding starts once the prepare period is over at the earliest
+            (400, None, 450, estimate(400 + 100 + 1_000 + 10)),
+            // Overdue for deciding, so queued: it can start no earlier than now
+            (0, None, 800, estimate(800 + 1_000 + 10)),
+        ];
+        for (submitted, deciding_since, current_block, expected) in cases {
+            assert_eq!(
+                split_vote_lock(
+                    &ongoing(submitted, deciding_since),
+                    current_block,
+                    Some(&TIMINGS)
+                ),
+                expected,
+                "submitted {}, deciding since {:?}, at {}",
+                submitted,
+                deciding_since,
+                current_block
+            );
+        }
+    }
+
+    #[test]
+    fn standard_votes_on_ongoing_referenda() {
+        let state = ongoing(0, Some(500));
+        let end = 500 + 1_000 + 10;
+        // (conviction, timings, expected unlock)
+        let cases = [
+            (1, Some(&TIMINGS), end + VOTE_LOCKING_PERIOD),
+            (2, Some(&TIMINGS), end + 2 * VOTE_LOCKING_PERIOD),
+            (0, Some(&TIMINGS), end),
+            // Without track timings the referendum is taken to end now
+            (1, None, 600 + VOTE_LOCKING_PERIOD),
+        ];
+        for (conviction, timings, expected) in cases {
+            for aye in [true, false] {
+                assert_eq!(
+                    standard_vote_lock(&state, aye, conviction, VOTE_LOCKING_PERIOD, 600, timings),
+                    LockStatus::PendingOutcome {
+                        worst_case_unlock: expected
+                    },
+                    "aye {}, conviction {}",
+                    aye,
+                    conviction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn split_votes_lock_until_the_referendum_ends() {
+        let end = 500 + 1_000 + 10;
+        let free = LockStatus::Unlockable;
+        // (state, expected)
+        let cases = [
+            (
+                ongoing(0, Some(500)),
+                LockStatus::PendingOutcome {
+                    worst_case_unlock: end,
+                },
+            ),
+            (ReferendumState::Approved { end: END }, free),
+            (ReferendumState::Rejected { end: END }, free),
+            (ReferendumState::Cancelled { end: END }, free),
+            (ReferendumState::TimedOut { end: END }, free),
+            (ReferendumState::Killed { end: END }, free),
+        ];
+        for (state, expected) in cases {
+            assert_eq!(
+                split_vote_lock(&state, 600, Some(&TIMINGS)),
+                expected,
+                "{:?}",
+                state
+            );
+        }
+    }
+
+    #[test]
+    fn split_votes_lock_their_whole_balance() {
+        // (aye, nay, abstain, expected)
+        let cases = [
+            (30, 20, 0, 50),
+            (30, 20, 10, 60),
+            (0, 0, 10, 10),
+            (u128::MAX, 1, 0, u128::MAX),
+        ];
+        for (aye, nay, abstain, expected) in cases {
+            assert_eq!(split_vote_balance(aye, nay, abstain), expected);
+        }
+    }
+
+    #[test]
+    fn delegations_stay_locked_until_undelegated() {
+        // (conviction, current_block, expected earliest unlock)
+        let cases = [
+            (0, END, END),
+            (1, END, END + VOTE_LOCKING_PERIOD),
+            (6, END, END + 32 * VOTE_LOCKING_PERIOD),
+            // The conviction period counts from undelegating, so it moves along with now
+            (1, END + 5_000, END + 5_000 + VOTE_LOCKING_PERIOD),
+        ];
+        for (conviction, current_block, expected) in cases {
+            let status = delegation_lock(conviction, VOTE_LOCKING_PERIOD, current_block);
+            assert_eq!(
+                status,
+                LockStatus::Delegated {
+                    earliest_unlock: expected
+                },
+                "conviction {}, at {}",
+                conviction,
+                current_block
+            );
+            assert_eq!(status.unlock_block(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn prior_locks_hold_until_their_block() {
+        // (block, current_block, expected)
+        let cases = [
+            (END, END - 1, LockStatus::LockedUntil { block: END }),
+            (END, END, LockStatus::Unlockable),
+            (END, END + 1, LockStatus::Unlockable),
+            (0, END, LockStatus::Unlockable),
+        ];
+        for (block, current_block, expected) in cases {
+            assert_eq!(
+                prior_lock(block, current_block),
+                expected,
+                "block {}, at {}",
+                block,
+                current_block
+            );
+        }
+    }
+}
@@ -1,296 +1,576 @@
-use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
-use handlebars::Handlebars;
-use handlebars::JsonValue;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use clap::Parser;
+use futures::{StreamExt, TryStreamExt};
+use handlebars::{Handlebars, JsonValue};
 use serde_json::json;
+use sp_core::crypto::{Ss58AddressFormat, Ss58Codec};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Cursor, Write};
-use std::process::Command;
+use std::io::{Cursor, IsTerminal, Write};
+use std::path::PathBuf;
 use std::str::FromStr;
+use subxt::constants::ConstantAddress;
+use subxt::storage::address::Yes;
+use subxt::storage::StorageAddress;
 use subxt::utils;
-use subxt::{Error, OnlineClient, PolkadotConfig};
-use chrono::prelude::*;
+
+mod balances;
+mod cache;
+mod chain;
+mod cli;
+mod clock;
+mod config;
+mod connection;
+mod conviction;
+mod csv;
+mod decode;
+mod ics;
+mod liquidity;
+mod locks;
+mod referenda;
+mod snapshot;
+mod staking;
+mod storage;
+mod table;
+mod timeline;
+mod totals;
+mod vesting;
+
+use balances::{BalanceSummary, Encumbrance, EncumbranceKind};
+use chain::Chain;
+use cli::{Cli, Commands, ReportArgs, ReportFormat};
+use clock::{BlockClock, BlockTime};
+use config::Config;
+use connection::NodeConnection;
+use decode::Decoding;
+use liquidity::Bucket;
+use locks::{
+    LockSource, LockStatus, LockedInterval, Referendum, ReferendumState, Release, TrackTimings,
+    VoteKind,
+};
+use snapshot::Snapshot;
+use staking::EraTimings;
+use storage::{
+    AccountData, AccountVote, ActiveEra, BalanceLock, PriorLock, StakingLedger, UnlockChunk,
+    VestingInfo, Voting,
+};
+use totals::LockTotals;
+use vesting::{VestingSchedule, VestingSummary};
 
 #[subxt::subxt(runtime_metadata_path = "./artifacts/polkadot_metadata_small.scale")]
 pub mod polkadot {}
 
-const BASE_LOCK_PERIOD: u32 = 28; // 28 days
-const PLANCKS_PER_DOT: f64 = 1e10;
-const MINUTES_PER_HOUR: i64 = 60;
-const HOURS_PER_DAY: i64 = 24;
-const SECONDS_PER_BLOCK: i64 = 6;
-const BLOCKS_TO_MINUTES_FACTOR: i64 = SECONDS_PER_BLOCK / 60; // This combines the constants
-const GENESIS_THRESHOLD: u32 = 9000000; // use a block number closer to genesis for early block time calculations
-
-fn get_conviction_multiplier(conviction: u8) -> u32 {
-    match conviction {
-        0..=6 => 1 << conviction,
-        _ => panic!("Unknown conviction value: {}", conviction),
-    }
-}
-
-fn plancks_to_dots<T: Into<f64>>(plancks: T) -> f64 {
-    plancks.into() / PLANCKS_PER_DOT
-}
-
-fn create_datetime_from_ymd(
-    year: i32,
-    month: u32,
-    day: u32,
-    hour: u32,
-    minute: u32,
-    second: u32,
-) -> DateTime<Utc> {
-    NaiveDate::from_ymd(year, month, day)
-        .and_hms(hour, minute, second)
-        .and_utc()
-}
-
-struct LockedInterval {
-    start_date: DateTime<Utc>,
-    end_date: DateTime<Utc>,
-    amount: f64,
+type ReferendumInfo = polkadot::runtime_types::pallet_referenda::types::ReferendumInfo<
+    u16,
+    polkadot::runtime_types::polkadot_runtime::OriginCaller,
+    u32,
+    polkadot::runtime_types::frame_support::traits::preimages::Bounded<
+        polkadot::runtime_types::polkadot_runtime::RuntimeCall,
+    >,
+    u128,
+    polkadot::runtime_types::pallet_conviction_voting::types::Tally<u128>,
+    utils::AccountId32,
+    (u32, u32),
+>;
+
+tokio::task_local! {
+    /// Lines printed about the account being processed, held back until it is done.
+    static ACCOUNT_OUTPUT: RefCell<String>;
 }
 
-impl LockedInterval {
-    fn overlaps_with(&self, start: &DateTime<Utc>, end: &DateTime<Utc>) -> bool {
-        !(self.end_date < *start || self.start_date > *end)
-    }
+/// Prints a line about the account being processed. While accounts are processed side by
+/// side, the line is held back and printed with the rest of the account's output once
+/// the account is done, so lines of different accounts don't interleave.
+macro_rules! account_println {
+    ($($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        let held = ACCOUNT_OUTPUT.try_with(|output| {
+            let mut output = output.borrow_mut();
+            output.push_str(&line);
+            output.push('\n');
+        });
+        if held.is_err() {
+            println!("{}", line);
+        }
+    }};
 }
 
-fn calculate_end_datetime(
-    base_block: u32,
-    current_block: u32,
-    conviction: u8,
-) -> (DateTime<Utc>, DateTime<Utc>) {
-    // The current_block_datetime is the current UTC time
-    let current_block_datetime = Utc::now();
-
-    // Calculate the difference in blocks and convert it into a time difference
-    let block_diff = (current_block - base_block) as i64;
-    let time_diff = Duration::seconds(block_diff * SECONDS_PER_BLOCK);
-
-    // Subtracting the time difference from the current time gives us the base_block_datetime
-    let base_block_datetime = current_block_datetime - time_diff;
-
-    let conviction_multiplier = get_conviction_multiplier(conviction) as i64;
-    let lock_period_in_minutes =
-        BASE_LOCK_PERIOD as i64 * conviction_multiplier * HOURS_PER_DAY * MINUTES_PER_HOUR;
+/// Version of the report model written by `--format json`. Bumped whenever a field is
+/// removed, renamed or changes meaning; adding fields doesn't change it.
+const REPORT_SCHEMA_VERSION: u32 = 1;
 
-    let end_datetime = base_block_datetime + Duration::minutes(lock_period_in_minutes);
-    (current_block_datetime, end_datetime)
+fn plancks_to_units<T: Into<f64>>(plancks: T) -> f64 {
+    chain::current().to_units(plancks.into())
 }
 
-fn update_lock_dates(
-    locked_intervals: &mut Vec<LockedInterval>,
-    start: DateTime<Utc>,
-    end: DateTime<Utc>,
-    amount: f64,
-) {
-    locked_intervals.push(LockedInterval {
-        start_date: start,
-        end_date: end,
-        amount,
-    });
+/// Start and end dates of a lock with the given status. Locks are counted from the
+/// current block; ones that are already unlockable end there too.
+async fn calculate_end_datetime(
+    clock: &BlockClock,
+    status: &LockStatus,
+) -> Result<(DateTime<Utc>, BlockTime), Box<dyn std::error::Error>> {
+    let end_block = status.unlock_block().unwrap_or(clock.current_block());
+    Ok((clock.current_time(), clock.block_time(end_block).await?))
 }
 
 async fn gather_and_cross_reference(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    clock: &BlockClock,
+    decoding: Decoding,
+    ladder: &[Bucket],
     key: &utils::AccountId32,
 ) -> Result<JsonValue, Box<dyn std::error::Error>> {
     // Initialize default values
-    let mut liquidity_data = json!({});
     let mut locked_intervals = Vec::new();
+    let mut class_locks_data = Vec::new();
 
     // Try fetching class locks and process them if available
-    if let Some(class_locks_data) = fetch_class_locks(api, key).await? {
-        let class_locks = class_locks_data.0.as_slice();
-
-        let current_block_number = fetch_current_block_number(api).await?;
-        locked_intervals = process_class_locks(api, key, class_locks, current_block_number).await?;
-        liquidity_data = display_liquidity_ladder(&locked_intervals)?;
+    if let Some(class_locks) = fetch_class_locks(api, decoding, key).await? {
+        let class_locks = class_locks.as_slice();
+        class_locks_data = class_locks
+            .iter()
+            .map(|(track, amount)| {
+                json!({
+                    "track": track,
+                    "amount": plancks_to_units(*amount as f64),
+                })
+            })
+            .collect();
+
+        let vote_locking_period = fetch_vote_locking_period()?;
+        let track_timings = fetch_track_timings(api, decoding)?;
+        locked_intervals = process_class_locks(
+            api,
+            decoding,
+            key,
+            class_locks,
+            clock,
+            vote_locking_period,
+            &track_timings,
+        )
+        .await?;
     }
 
-    let lock_totals_data = display_lock_totals(api, key).await?;
-    let vesting_data = display_vesting_info(api, key).await?;
+    locked_intervals.extend(process_staking(api, clock, decoding, key).await?);
 
-    // Combine data and return
-    Ok(json!({
-        "liquidity": liquidity_data,
-        "locks": lock_totals_data,
+    let locks = fetch_account_locks(api, decoding, key).await?;
+    let (vesting_data, vesting_intervals) =
+        display_vesting_info(api, clock, decoding, key, locks.as_deref()).await?;
+    locked_intervals.extend(vesting_intervals);
+
+    let lock_totals = display_lock_totals(locks.as_deref());
+    let (balance, encumbrances) = display_balances(api, decoding, key, locks.as_deref()).await?;
+
+    let sections = json!({
+        "locks": lock_totals,
+        "class_locks": class_locks_data,
+        "balance": balance,
+        "encumbrances": encumbrances,
         "vesting": vesting_data,
-    }))
+    });
+    let now = clock.current_time();
+    account_report(chain::current(), sections, &locked_intervals, now, ladder)
 }
 
-async fn fetch_current_block_number(
-    api: &OnlineClient<PolkadotConfig>,
-) -> Result<u32, Box<dyn std::error::Error>> {
-    let mut blocks_sub = api.blocks().subscribe_finalized().await?;
-    if let Some(block) = blocks_sub.next().await {
-        Ok(block?.header().number)
-    } else {
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Failed to fetch block.",
-        )))
+/// Adds the liquidity ladder and timeline of the account's locked intervals, counted from
+/// `now`, to the sections read for it. Sections the chain has no data for stay empty.
+fn account_report(
+    chain: &Chain,
+    mut sections: JsonValue,
+    locked_intervals: &[LockedInterval],
+    now: DateTime<Utc>,
+    ladder: &[Bucket],
+) -> Result<JsonValue, Box<dyn std::error::Error>> {
+    sections["liquidity"] = display_liquidity_ladder(chain, locked_intervals, now, ladder)?;
+    sections["locked_over_time"] =
+        serde_json::to_value(liquidity::locked_over_time(locked_intervals, now))?;
+    sections["intervals"] = serde_json::to_value(locked_intervals)?;
+    sections["delegations"] = delegations_by_track(locked_intervals);
+    Ok(sections)
+}
+
+/// Recounts a cached report from `now` with the configured ladder. Each account's ladder
+/// and timeline are rebuilt from its stored locked intervals, so amounts move to nearer
+/// buckets as time passes, except earliest unlocks, which move along with `now`. The
+/// balances and locks stay those of the cached snapshot.
+fn refresh_report(
+    report: &mut JsonValue,
+    chain: &Chain,
+    now: DateTime<Utc>,
+    ladder: &[Bucket],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let now_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
+    report["date"] = now_str.clone().into();
+    report["counted_from"] = now_str.into();
+    report["offline"] = true.into();
+    report["ladder"] = serde_json::to_value(ladder.iter().rev().collect::<Vec<_>>())?;
+    for account in report["accounts"].as_array_mut().into_iter().flatten() {
+        let sections = account["data"].take();
+        let mut intervals: Vec<LockedInterval> =
+            serde_json::from_value(sections["intervals"].clone())
+                .map_err(|e| format!("Cached report can't be rebuilt ({}), run it online", e))?;
+        for interval in &mut intervals {
+            interval.recount_from(now, chain.block_time_ms);
+        }
+        account["data"] = account_report(chain, sections, &intervals, now, ladder)?;
     }
+    Ok(())
+}
+
+fn fetch_vote_locking_period() -> Result<u32, Box<dyn std::error::Error>> {
+    let chain = chain::current();
+    chain
+        .vote_locking_period
+        .ok_or_else(|| format!("No VoteLockingPeriod known for chain {}", chain.name).into())
+}
+
+fn fetch_track_timings(
+    api: &Snapshot,
+    decoding: Decoding,
+) -> Result<HashMap<u16, TrackTimings>, Box<dyn std::error::Error>> {
+    let constant_query = polkadot::constants().referenda().tracks();
+    let tracks = constant_decoded(
+        api,
+        decoding,
+        &constant_query,
+        |tracks| {
+            tracks
+                .into_iter()
+                .map(|(track, info)| {
+                    let timings = TrackTimings {
+                        prepare_period: info.prepare_period,
+                        decision_period: info.decision_period,
+                        confirm_period: info.confirm_period,
+                    };
+                    (track, timings)
+                })
+                .collect::<Vec<_>>()
+        },
+        |value| Some(decode::tracks(value)),
+    )?;
+
+    Ok(tracks.unwrap_or_default().into_iter().collect())
 }
 
 async fn process_class_locks(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    decoding: Decoding,
     key: &utils::AccountId32,
     class_locks: &[(u16, u128)],
-    current_block_number: u32,
+    clock: &BlockClock,
+    vote_locking_period: u32,
+    track_timings: &HashMap<u16, TrackTimings>,
 ) -> Result<Vec<LockedInterval>, Box<dyn std::error::Error>> {
-    let mut locked_intervals: Vec<LockedInterval> = Vec::new();
+    let per_track = futures::stream::iter(class_locks)
+        .map(|class_lock| async move {
+            let mut locked_intervals = Vec::new();
+            let votes_data = fetch_voting(api, decoding, key, class_lock.0).await?;
+
+            match votes_data {
+                Some(Voting::Casting { votes, prior }) => {
+                    push_prior_lock(class_lock.0, &prior, clock, &mut locked_intervals).await?;
+                    let votes = process_casting_votes(
+                        api,
+                        decoding,
+                        class_lock.0,
+                        &votes,
+                        clock,
+                        vote_locking_period,
+                        track_timings.get(&class_lock.0),
+                    )
+                    .await?;
+                    locked_intervals.extend(votes);
+                }
+                Some(Voting::Delegating {
+                    balance,
+                    target,
+                    conviction,
+                    prior,
+                }) => {
+                    push_prior_lock(class_lock.0, &prior, clock, &mut locked_intervals).await?;
+                    let status = locks::delegation_lock(
+                        conviction,
+                        vote_locking_period,
+                        clock.current_block(),
+                    );
+                    let (start_date, end) = calculate_end_datetime(clock, &status).await?;
+
+                    locked_intervals.push(LockedInterval {
+                        start_date,
+                        end_date: end.date,
+                        end_date_margin_secs: end.margin_secs,
+                        release: Release::AtEnd,
+                        amount: plancks_to_units(balance as f64),
+                        plancks: balance,
+                        status,
+                        source: LockSource::Delegation {
+                            track: class_lock.0,
+                            target: format_address(&target),
+                            conviction,
+                        },
+                    });
+                }
+                None => {}
+            }
+            Ok::<_, Box<dyn std::error::Error>>(locked_intervals)
+        })
+        .buffered(api.parallelism())
+        .try_collect::<Vec<_>>()
+        .await?;
 
-    for class_lock in class_locks {
-        let votes_data = fetch_voting(api, key, class_lock.0).await?;
+    Ok(per_track.into_iter().flatten().collect())
+}
 
-        if let Some(polkadot::runtime_types::pallet_conviction_voting::vote::Voting::Casting(
-            casting,
-        )) = votes_data
-        {
-            process_casting_votes(
-                api,
-                key,
-                &casting,
-                current_block_number,
-                &mut locked_intervals,
-            )
-            .await?;
-        }
+async fn process_casting_votes(
+    api: &Snapshot,
+    decoding: Decoding,
+    track: u16,
+    votes: &[(u32, AccountVote)],
+    clock: &BlockClock,
+    vote_locking_period: u32,
+    track_timings: Option<&TrackTimings>,
+) -> Result<Vec<LockedInterval>, Box<dyn std::error::Error>> {
+    let mut locked_intervals = Vec::new();
+    let current_block_number = clock.current_block();
+    let referenda =
+        fetch_referenda(api, decoding, votes.iter().map(|(ref_num, _)| *ref_num)).await?;
+
+    for ((ref_num, vote_detail), referendum) in votes.iter().zip(referenda) {
+        let Some(state) = referendum.map(|referendum| referendum.state) else {
+            eprintln!("[Error] No referendum info for referendum {}", ref_num);
+            continue;
+        };
+
+        let (status, balance, vote) = match *vote_detail {
+            AccountVote::Standard {
+                aye,
+                conviction,
+                balance,
+            } => {
+                let status = locks::standard_vote_lock(
+                    &state,
+                    aye,
+                    conviction,
+                    vote_locking_period,
+                    current_block_number,
+                    track_timings,
+                );
+                let vote = VoteKind::Standard {
+                    direction: if aye { "aye" } else { "nay" }.to_string(),
+                    conviction,
+                };
+                (status, balance, vote)
+            }
+            AccountVote::Split { aye, nay } => {
+                let status = locks::split_vote_lock(&state, current_block_number, track_timings);
+                let vote = VoteKind::Split {
+                    aye: plancks_to_units(aye as f64),
+                    nay: plancks_to_units(nay as f64),
+                };
+                (status, locks::split_vote_balance(aye, nay, 0), vote)
+            }
+            AccountVote::SplitAbstain { aye, nay, abstain } => {
+                let status = locks::split_vote_lock(&state, current_block_number, track_timings);
+                let vote = VoteKind::SplitAbstain {
+                    aye: plancks_to_units(aye as f64),
+                    nay: plancks_to_units(nay as f64),
+                    abstain: plancks_to_units(abstain as f64),
+                };
+                (status, locks::split_vote_balance(aye, nay, abstain), vote)
+            }
+        };
+
+        let (start_date, end) = calculate_end_datetime(clock, &status).await?;
+
+        locked_intervals.push(LockedInterval {
+            start_date,
+            end_date: end.date,
+            end_date_margin_secs: end.margin_secs,
+            release: Release::AtEnd,
+            amount: plancks_to_units(balance as f64),
+            plancks: balance,
+            status,
+            source: LockSource::Vote {
+                referendum: *ref_num,
+                track,
+                referendum_state: state.label().to_string(),
+                vote,
+            },
+        });
     }
 
     Ok(locked_intervals)
 }
 
-async fn process_casting_votes(
-    api: &OnlineClient<PolkadotConfig>,
-    key: &utils::AccountId32,
-    casting: &polkadot::runtime_types::pallet_conviction_voting::vote::Casting<u128, u32, u32>,
-    current_block_number: u32,
+async fn push_prior_lock(
+    track: u16,
+    prior: &PriorLock,
+    clock: &BlockClock,
     locked_intervals: &mut Vec<LockedInterval>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    for (ref_num, vote_detail) in casting.votes.0.as_slice().iter() {
-        let ref_data = fetch_referendum_info(api, key, *ref_num).await?;
-
-        let block_number = match ref_data {
-            Some(data) => match data {
-                polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Ongoing(
-                    status,
-                ) => status.submitted,
-                polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Approved(
-                    block_number,
-                    ..,
-                ) => block_number,
-                polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Rejected(
-                    block_number,
-                    ..,
-                ) => block_number,
-                polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Killed(
-                    block_number,
-                    ..,
-                ) => block_number,
-                polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Cancelled(
-                    block_number,
-                    ..,
-                ) => block_number,
-                polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::TimedOut(
-                    block_number,
-                    ..,
-                ) => block_number,
-                _ => 0, // For other `ReferendumInfo` variants, if there are any
-            },
-            None => 0, // Handle the case where ref_data is None
-        };
-
-        if block_number != 0 {
-            // Make sure we have a valid block_number
-            if let polkadot::runtime_types::pallet_conviction_voting::vote::AccountVote::Standard { vote, balance } = vote_detail {
-                let conviction = vote.0 % 128;
-                let (base_block_date, end_datetime) = calculate_end_datetime(block_number, current_block_number, conviction);
-                let locked_amount_in_dot = *balance as f64 / 1e10;
-                update_lock_dates(locked_intervals, base_block_date, end_datetime, locked_amount_in_dot);
-            }
-        }
+    let (block, balance) = (prior.block, prior.balance);
+    if balance == 0 {
+        return Ok(());
     }
 
+    let status = locks::prior_lock(block, clock.current_block());
+    let (start_date, end) = calculate_end_datetime(clock, &status).await?;
+
+    locked_intervals.push(LockedInterval {
+        start_date,
+        end_date: end.date,
+        end_date_margin_secs: end.margin_secs,
+        release: Release::AtEnd,
+        amount: plancks_to_units(balance as f64),
+        plancks: balance,
+        status,
+        source: LockSource::PriorLock { track },
+    });
     Ok(())
 }
 
-fn categorize_lock_period(end_date: DateTime<Utc>) -> &'static str {
-    let duration_from_now_in_days = (end_date - Utc::now()).num_days();
-    match duration_from_now_in_days {
-        d if d <= 0 => "Locked 0 Days",
-        1..=7 => "Locked 1-7 Days",
-        8..=14 => "Locked 8-14 Days",
-        15..=28 => "Locked 15-28 Days",
-        29..=60 => "Locked 29-60 Days",
-        _ => "Locked 60+ Days",
+fn conviction_index(
+    conviction: &polkadot::runtime_types::pallet_conviction_voting::conviction::Conviction,
+) -> u8 {
+    use polkadot::runtime_types::pallet_conviction_voting::conviction::Conviction;
+
+    match conviction {
+        Conviction::None => 0,
+        Conviction::Locked1x => 1,
+        Conviction::Locked2x => 2,
+        Conviction::Locked3x => 3,
+        Conviction::Locked4x => 4,
+        Conviction::Locked5x => 5,
+        Conviction::Locked6x => 6,
     }
 }
 
-fn display_liquidity_ladder(
-    locked_intervals: &[LockedInterval],
-) -> Result<JsonValue, Box<dyn std::error::Error>> {
-    let mut categorized_amounts: HashMap<&'static str, (f64, DateTime<Utc>)> = HashMap::new();
+fn referendum_from_static(info: ReferendumInfo) -> Referendum {
+    use polkadot::runtime_types::pallet_referenda::types::ReferendumInfo as Info;
+
+    let state = match info {
+        Info::Ongoing(status) => {
+            return Referendum {
+                state: ReferendumState::Ongoing {
+                    track: status.track,
+                    submitted: status.submitted,
+                    deciding_since: status.deciding.map(|deciding| deciding.since),
+                },
+                tally: Some((status.tally.ayes, status.tally.nays)),
+            }
+        }
+        Info::Approved(end, ..) => ReferendumState::Approved { end },
+        Info::Rejected(end, ..) => ReferendumState::Rejected { end },
+        Info::Cancelled(end, ..) => ReferendumState::Cancelled { end },
+        Info::TimedOut(end, ..) => ReferendumState::TimedOut { end },
+        Info::Killed(end) => ReferendumState::Killed { end },
+    };
+    Referendum { state, tally: None }
+}
 
-    for interval in locked_intervals {
-        let category = categorize_lock_period(interval.end_date);
-        let entry = categorized_amounts.entry(category).or_default();
-        println!(
-            "Interval amount: {:.10}, End date: {}, Category: {}",
-            interval.amount, interval.end_date, category
-        );
+fn voting_from_static(
+    voting: polkadot::runtime_types::pallet_conviction_voting::vote::Voting<
+        u128,
+        utils::AccountId32,
+        u32,
+        u32,
+    >,
+) -> Voting {
+    use polkadot::runtime_types::pallet_conviction_voting::vote::{
+        AccountVote as Vote, Voting as V,
+    };
 
-        if interval.amount > entry.0
-            || (f64::abs(interval.amount - entry.0) < f64::EPSILON && interval.end_date > entry.1)
-        {
-            *entry = (interval.amount, interval.end_date);
-        }
+    match voting {
+        V::Casting(casting) => Voting::Casting {
+            votes: casting
+                .votes
+                .0
+                .into_iter()
+                .map(|(ref_num, vote)| {
+                    let vote = match vote {
+                        Vote::Standard { vote, balance } => AccountVote::standard(vote.0, balance),
+                        Vote::Split { aye, nay } => AccountVote::Split { aye, nay },
+                        Vote::SplitAbstain { aye, nay, abstain } => {
+                            AccountVote::SplitAbstain { aye, nay, abstain }
+                        }
+                    };
+                    (ref_num, vote)
+                })
+                .collect(),
+            prior: PriorLock {
+                block: casting.prior.0,
+                balance: casting.prior.1,
+            },
+        },
+        V::Delegating(delegating) => Voting::Delegating {
+            balance: delegating.balance,
+            target: delegating.target,
+            conviction: conviction_index(&delegating.conviction),
+            prior: PriorLock {
+                block: delegating.prior.0,
+                balance: delegating.prior.1,
+            },
+        },
+        V::__Ignore(_) => unreachable!("never decoded"),
     }
+}
 
-    let lock_order = [
-        "Locked 0 Days",
-        "Locked 1-7 Days",
-        "Locked 8-14 Days",
-        "Locked 15-28 Days",
-        "Locked 29-60 Days",
-        "Locked 60+ Days",
-    ];
+fn delegations_by_track(locked_intervals: &[LockedInterval]) -> JsonValue {
+    let delegations: Vec<JsonValue> = locked_intervals
+        .iter()
+        .filter_map(|interval| match &interval.source {
+            LockSource::Delegation {
+                track,
+                target,
+                conviction,
+            } => Some(json!({
+                "track": track,
+                "target": target,
+                "conviction": conviction,
+                "amount": format!("{:.10}", interval.amount),
+                "earliest_unlock": interval.end_date.format("%Y-%m-%d").to_string(),
+            })),
+            _ => None,
+        })
+        .collect();
+    JsonValue::Array(delegations)
+}
+
+/// Buckets the locked intervals by how long they stay locked, counted from `now`.
+fn display_liquidity_ladder(
+    chain: &Chain,
+    locked_intervals: &[LockedInterval],
+    now: DateTime<Utc>,
+    ladder: &[Bucket],
+) -> Result<JsonValue, Box<dyn std::error::Error>> {
+    let boundaries: Vec<DateTime<Utc>> = ladder
+        .iter()
+        .filter_map(|bucket| Some(now + chrono::Duration::days(bucket.until_day?.into())))
+        .collect();
+    // Locks overlap, so each bucket shows what becomes newly liquid in it rather than
+    // the intervals ending in it
+    let released = liquidity::released_between(locked_intervals, now, &boundaries);
+    let prior_lock = liquidity::released_by_prior_locks(locked_intervals, now, &boundaries);
 
-    let mut max_lock_amount = 0.0;
     let mut account_data = vec![];
 
     // Gather data to be passed to the template
-    for &lock_category in lock_order.iter().rev() {
-        if let Some(&(amount, _)) = categorized_amounts.get(lock_category) {
-            if amount > max_lock_amount {
-                max_lock_amount = amount;
-            }
-
-            let class = match lock_category {
-                "Locked 0 Days" => "locked-0-days",
-                "Locked 1-7 Days" => "locked-1-7-days",
-                "Locked 8-14 Days" => "locked-8-14-days",
-                "Locked 15-28 Days" => "locked-15-28-days",
-                "Locked 29-60 Days" => "locked-29-60-days",
-                _ => "locked-60-plus-days",
-            };
-            println!(
-                "Lock Category: {}, Amount: {:.10}, Class: {}",
-                lock_category, amount, class
-            );
+    for ((bucket, &plancks), &prior_lock) in ladder.iter().zip(&released).zip(&prior_lock).rev() {
+        if plancks > 0 {
+            let amount = chain.to_units(plancks as f64);
             account_data.push(json!({
-                "lock_category": lock_category,
+                "lock_category": bucket.label,
                 "amount": format!("{:.10}", amount),
-                "class": class.to_string(),
+                "plancks": plancks.to_string(),
+                "class": bucket.class,
+                "prior_lock": prior_lock,
             }));
         } else {
             account_data.push(json!({
-                "lock_category": lock_category,
+                "lock_category": bucket.label,
                 "amount": "none",
+                "plancks": "0",
                 "class": "none",
             }));
         }
@@ -302,405 +582,956 @@ fn display_liquidity_ladder(
     Ok(account_data_for_address)
 }
 
-fn generate_html_for_all_addresses(
+/// Writes the report for all accounts in the requested format.
+fn write_report(
     all_addresses_data: &serde_json::Value,
+    args: &ReportArgs,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if args.format == ReportFormat::Table && args.output.is_none() {
+        let colour = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        print!("\n{}", table::terminal(all_addresses_data, colour));
+        return Ok(());
+    }
+
+    let filename = match &args.output {
+        Some(path) => path.clone(),
+        None => {
+            // Generate current date and time string
+            let local: DateTime<Local> = Local::now();
+            let timestamp_str = local.format("%Y-%m-%d_%H-%M-%S").to_string();
+
+            // Create a filename with the current date and time
+            PathBuf::from(format!(
+                "liquidity_matrix_all_addresses_{}.{}",
+                timestamp_str,
+                args.format.extension()
+            ))
+        }
+    };
+
+    let files = match args.format {
+        ReportFormat::Html => vec![(filename.clone(), render_html(all_addresses_data)?)],
+        ReportFormat::Json => vec![(
+            filename.clone(),
+            serde_json::to_string_pretty(all_addresses_data)?,
+        )],
+        ReportFormat::Csv => {
+            let tables = csv::tables(all_addresses_data)?;
+            let stem = filename.with_extension("");
+            let table_path = |table: &str| {
+                let mut path = stem.clone().into_os_string();
+                path.push(format!("_{}.csv", table));
+                PathBuf::from(path)
+            };
+            vec![
+                (table_path("ladder"), tables.ladder),
+                (table_path("intervals"), tables.intervals),
+            ]
+        }
+        ReportFormat::Table => vec![(filename.clone(), table::terminal(all_addresses_data, false))],
+        ReportFormat::Markdown => vec![(filename.clone(), table::markdown(all_addresses_data))],
+        ReportFormat::Ics => vec![(
+            filename.clone(),
+            ics::calendar(all_addresses_data, Utc::now())?,
+        )],
+    };
+
+    for (path, contents) in &files {
+        let mut file = File::create(path)?;
+        file.write_all(contents.as_bytes())?;
+        println!("Generated report at {}", path.display());
+    }
+
+    if args.format == ReportFormat::Html && !args.no_open {
+        if let Err(e) = cli::open_in_browser(&filename) {
+            eprintln!("[Error] Failed to open {}: {}", filename.display(), e);
+        }
+    }
+
+    Ok(())
+}
+
+fn render_html(
+    all_addresses_data: &serde_json::Value,
+) -> Result<String, Box<dyn std::error::Error>> {
     let reg = Handlebars::new();
     let template_string = include_str!("../templates/liquidity_matrix.html");
 
+    // Timelines are drawn from the model, counting from the snapshot's block, or from when
+    // an offline report was rebuilt
+    let mut data = all_addresses_data.clone();
+    let now = data["counted_from"]
+        .as_str()
+        .or(data["snapshot"]["date"].as_str())
+        .and_then(|date| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").ok())
+        .map(|date| Utc.from_utc_datetime(&date))
+        .unwrap_or_else(Utc::now);
+    let symbol = data["chain"]["symbol"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    for account in data["accounts"].as_array_mut().into_iter().flatten() {
+        account["timeline"] = timeline::svg(&account["data"], now, &symbol).into();
+    }
+    data["lock_columns"] = totals::column_headers().into();
+
     let mut cursor = Cursor::new(Vec::new());
-    reg.render_template_to_write(&template_string, &all_addresses_data, &mut cursor)?;
+    reg.render_template_to_write(template_string, &data, &mut cursor)?;
 
-    let rendered_html = String::from_utf8(cursor.into_inner())?;
+    Ok(String::from_utf8(cursor.into_inner())?)
+}
 
-    // Generate current date and time string
-    let local: DateTime<Local> = Local::now();
-    let timestamp_str = local.format("%Y-%m-%d_%H-%M-%S").to_string();
+/// Prints the account's balance locks and breaks them down by lock ID.
+fn display_lock_totals(locks: Option<&[BalanceLock]>) -> LockTotals {
+    let Some(locks_data) = locks else {
+        return LockTotals::empty();
+    };
 
-    // Create a filename with the current date and time
-    let filename = format!("liquidity_matrix_all_addresses_{}.html", timestamp_str);
+    let totals = LockTotals::new(
+        locks_data
+            .iter()
+            .map(|lock| (lock.id, plancks_to_units(lock.amount as f64))),
+    );
+
+    account_println!("Lock totals:");
+    for lock in &totals.locks {
+        account_println!(
+            "Lock ID: {}, {}, Amount: {:.10} {symbol}",
+            lock.id,
+            lock.label,
+            lock.amount,
+            symbol = chain::current().symbol
+        );
+    }
+    if let Some(dominant) = totals.dominant() {
+        account_println!(
+            "Frozen: {:.10} {symbol}, set by the {} lock",
+            totals.frozen,
+            dominant.label,
+            symbol = chain::current().symbol
+        );
+    }
 
-    let mut file = File::create(&filename)?;
-    file.write_all(rendered_html.as_bytes())?;
+    totals
+}
 
-    println!("Generated heatmap at {}", filename);
-    Command::new("open")
-        .arg(&filename)
-        .status()?;
+/// Prints the account's free, reserved and frozen balance, and every lock, hold and freeze
+/// on it.
+async fn display_balances(
+    api: &Snapshot,
+    decoding: Decoding,
+    key: &utils::AccountId32,
+    locks: Option<&[BalanceLock]>,
+) -> Result<(BalanceSummary, Vec<Encumbrance>), Box<dyn std::error::Error>> {
+    let balance = fetch_account_balance(api, decoding, key)
+        .await?
+        .map(|account| {
+            BalanceSummary::new(
+                plancks_to_units(account.free as f64),
+                plancks_to_units(account.reserved as f64),
+                plancks_to_units(account.frozen as f64),
+            )
+        })
+        .unwrap_or_default();
+
+    let mut encumbrances = Vec::new();
+    if let Some(locks) = locks {
+        encumbrances.extend(
+            locks
+                .iter()
+                .map(|lock| Encumbrance::lock(&lock.id, plancks_to_units(lock.amount as f64))),
+        );
+    }
+    encumbrances.extend(fetch_encumbrances(api, key, EncumbranceKind::Hold).await?);
+    encumbrances.extend(fetch_encumbrances(api, key, EncumbranceKind::Freeze).await?);
+
+    account_println!(
+        "Balance: Free: {:.10} {symbol}, Reserved: {:.10} {symbol}, Frozen: {:.10} {symbol}, Transferable: {:.10} {symbol}",
+        balance.free, balance.reserved, balance.frozen, balance.transferable,
+        symbol = chain::current().symbol
+    );
+    for encumbrance in &encumbrances {
+        account_println!(
+            "{:?}: {}, Reason: {}, Amount: {:.10} {symbol}",
+            encumbrance.kind,
+            encumbrance.pallet,
+            encumbrance.reason,
+            encumbrance.amount,
+            symbol = chain::current().symbol
+        );
+    }
 
-    Ok(())
+    Ok((balance, encumbrances))
 }
 
-async fn display_lock_totals(
-    api: &OnlineClient<PolkadotConfig>,
+async fn display_class_locks(
+    api: &Snapshot,
+    decoding: Decoding,
     key: &utils::AccountId32,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(locks_data) = fetch_account_locks(api, key).await? {
-        let locks = locks_data.0.as_slice();
-
-        println!("Lock totals:");
-        for lock in locks {
-            if let Ok(id_str) = String::from_utf8(lock.id.to_vec()) {
-                let amount_in_dot = lock.amount as f64 / 1e10;
-                println!("Lock ID: {}, Amount: {:.10} DOT", id_str, amount_in_dot);
-            } else {
-                println!("Failed to convert lock id to string");
-            }
+    if let Some(class_locks_data) = fetch_class_locks(api, decoding, key).await? {
+        account_println!("Class locks:");
+        for (class, amount) in class_locks_data.iter() {
+            account_println!(
+                "Track: {}, Amount: {:.10} {symbol}",
+                class,
+                plancks_to_units(*amount as f64),
+                symbol = chain::current().symbol
+            );
         }
+    } else {
+        account_println!("No class locks for the account.");
     }
 
     Ok(())
 }
-/*
+
+fn print_prior_lock(prior: &PriorLock) {
+    if prior.balance > 0 {
+        account_println!(
+            "Prior lock: {:.10} {symbol} until block {}",
+            plancks_to_units(prior.balance as f64),
+            prior.block,
+            symbol = chain::current().symbol
+        );
+    }
+}
+
 async fn gather_detailed_vote_info(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    decoding: Decoding,
     key: &utils::AccountId32,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(class_locks_data) = fetch_class_locks(api, decoding, key).await? else {
+        account_println!("No votes for the account.");
+        return Ok(());
+    };
 
-let class_locks_opt = fetch_class_locks(api, key).await?;
-if let Some(class_locks_data) = class_locks_opt {
-    let class_locks = class_locks_data.0.as_slice();
-
-    for class_lock in class_locks {
-        let votes_data = fetch_voting(api, key, class_lock.0).await?;
+    for (class, _) in class_locks_data.iter() {
+        let votes_data = fetch_voting(api, decoding, key, *class).await?;
 
-        if let polkadot::runtime_types::pallet_conviction_voting::vote::Voting::Casting(casting) =
-            votes_data
-        {
+        if let Some(Voting::Casting { votes, prior }) = &votes_data {
             let mut referendums_with_details = vec![];
-
-            for (ref_num, vote_detail) in casting.votes.0.as_slice().iter() {
-                let ref_data = fetch_referendum_info(api, key, *ref_num).await?;
-
-                let (message, block_number) = match &ref_data {
-                    polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Ongoing(
-                        status,
-                    ) => {
-                        let ayes = status.tally.ayes as f64 / 1e10;
-                        let nays = status.tally.nays as f64 / 1e10;
-
-                        let detail = match vote_detail {
-                            polkadot::runtime_types::pallet_conviction_voting::vote::AccountVote::Standard { vote, balance } => {
-                                let conviction = vote.0 % 128;
-                                let vote_type = if vote.0 >= 128 { "aye" } else { "nay" };
-                                let amount_in_dot = *balance as f64 / 1e10;
-                                format!("Referendum: {}, {}x conviction, Vote: {}, Amount: {:.10} DOT, Tally: Ayes: {:.10} DOT, Nays: {:.10} DOT",
-                                        ref_num, conviction, vote_type, amount_in_dot, ayes, nays)
-                            },
-                            polkadot::runtime_types::pallet_conviction_voting::vote::AccountVote::Split { aye, nay } => {
-                                let aye_amount_in_dot = *aye as f64 / 1e10;
-                                let nay_amount_in_dot = *nay as f64 / 1e10;
-                                format!("Referendum: {}, Split vote, Aye Amount: {:.10} DOT, Nay Amount: {:.10} DOT, Tally: Ayes: {:.10} DOT, Nays: {:.10} DOT",
-                                        ref_num, aye_amount_in_dot, nay_amount_in_dot, ayes, nays)
-                            },
-                            polkadot::runtime_types::pallet_conviction_voting::vote::AccountVote::SplitAbstain { aye, nay, abstain } => {
-                                let aye_amount_in_dot = *aye as f64 / 1e10;
-                                let nay_amount_in_dot = *nay as f64 / 1e10;
-                                let abstain_amount_in_dot = *abstain as f64 / 1e10;
-                                format!("Referendum: {}, Split Abstain, Aye Amount: {:.10} DOT, Nay Amount: {:.10} DOT, Abstain Amount: {:.10} DOT, Tally: Ayes: {:.10} DOT, Nays: {:.10} DOT",
-                                        ref_num, aye_amount_in_dot, nay_amount_in_dot, abstain_amount_in_dot, ayes, nays)
-                            },
-                            _ => format!("Referendum: {}, unknown conviction, Tally: Ayes: {:.10} DOT, Nays: {:.10} DOT", ref_num, ayes, nays)
-                        };
-                        (detail, status.submitted)
+            let ref_nums = votes.iter().map(|(ref_num, _)| *ref_num);
+            let referenda = fetch_referenda(api, decoding, ref_nums).await?;
+
+            for ((ref_num, vote_detail), ref_data) in votes.iter().zip(referenda) {
+                let message = match &ref_data {
+                    Some(Referendum {
+                        state: ReferendumState::Ongoing { .. },
+                        tally,
+                    }) => {
+                        let (ayes, nays) = tally.unwrap_or_default();
+                        let ayes = plancks_to_units(ayes as f64);
+                        let nays = plancks_to_units(nays as f64);
+
+                        match vote_detail {
+                            AccountVote::Standard {
+                                aye,
+                                conviction,
+                                balance,
+                            } => {
+                                let vote_type = if *aye { "aye" } else { "nay" };
+                                let amount_in_units = plancks_to_units(*balance as f64);
+                                format!("Referendum: {}, {}x conviction, Vote: {}, Amount: {:.10} {symbol}, Tally: Ayes: {:.10} {symbol}, Nays: {:.10} {symbol}",
+                                        ref_num, conviction, vote_type, amount_in_units, ayes, nays, symbol = chain::current().symbol)
+                            }
+                            AccountVote::Split { aye, nay } => {
+                                let aye_amount_in_units = plancks_to_units(*aye as f64);
+                                let nay_amount_in_units = plancks_to_units(*nay as f64);
+                                format!("Referendum: {}, Split vote, Aye Amount: {:.10} {symbol}, Nay Amount: {:.10} {symbol}, Tally: Ayes: {:.10} {symbol}, Nays: {:.10} {symbol}",
+                                        ref_num, aye_amount_in_units, nay_amount_in_units, ayes, nays, symbol = chain::current().symbol)
+                            }
+                            AccountVote::SplitAbstain { aye, nay, abstain } => {
+                                let aye_amount_in_units = plancks_to_units(*aye as f64);
+                                let nay_amount_in_units = plancks_to_units(*nay as f64);
+                                let abstain_amount_in_units = plancks_to_units(*abstain as f64);
+                                format!("Referendum: {}, Split Abstain, Aye Amount: {:.10} {symbol}, Nay Amount: {:.10} {symbol}, Abstain Amount: {:.10} {symbol}, Tally: Ayes: {:.10} {symbol}, Nays: {:.10} {symbol}",
+                                        ref_num, aye_amount_in_units, nay_amount_in_units, abstain_amount_in_units, ayes, nays, symbol = chain::current().symbol)
+                            }
+                        }
                     }
-                    polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Approved(
-                        block_number,
-                        ..,
-                    ) => (
-                        format!("Referendum: {}, was accepted.", ref_num),
-                        *block_number,
-                    ),
-                    polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Rejected(
-                        block_number,
-                        ..,
-                    ) => (
-                        format!("Referendum: {}, was rejected.", ref_num),
-                        *block_number,
-                    ),
-                    polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Killed(
-                        block_number,
-                        ..,
-                    ) => (
-                        format!("Referendum: {}, was killed.", ref_num),
-                        *block_number,
-                    ),
-                    polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::Cancelled(
-                        block_number,
-                        ..,
-                    ) => (
-                        format!("Referendum: {}, was cancelled.", ref_num),
-                        *block_number,
-                    ),
-                    polkadot::runtime_types::pallet_referenda::types::ReferendumInfo::TimedOut(
-                        block_number,
-                        ..,
-                    ) => (
-                        format!("Referendum: {}, timed out.", ref_num),
-                        *block_number,
-                    ),
-                    _ => (format!("Referendum: {}, had unknown status.", ref_num), 0),
+                    Some(Referendum {
+                        state: ReferendumState::Approved { .. },
+                        ..
+                    }) => {
+                        format!("Referendum: {}, was accepted.", ref_num)
+                    }
+                    Some(Referendum {
+                        state: ReferendumState::Rejected { .. },
+                        ..
+                    }) => {
+                        format!("Referendum: {}, was rejected.", ref_num)
+                    }
+                    Some(Referendum {
+                        state: ReferendumState::Killed { .. },
+                        ..
+                    }) => {
+                        format!("Referendum: {}, was killed.", ref_num)
+                    }
+                    Some(Referendum {
+                        state: ReferendumState::Cancelled { .. },
+                        ..
+                    }) => {
+                        format!("Referendum: {}, was cancelled.", ref_num)
+                    }
+                    Some(Referendum {
+                        state: ReferendumState::TimedOut { .. },
+                        ..
+                    }) => {
+                        format!("Referendum: {}, timed out.", ref_num)
+                    }
+                    None => format!("Referendum: {}, had unknown status.", ref_num),
                 };
 
-                //println!("Block Number: {}", block_number); // Print block number here
                 referendums_with_details.push(message);
             }
+
+            account_println!("Track {}:", class);
+            print_prior_lock(prior);
             for info in &referendums_with_details {
-                println!("{}", info);
+                account_println!("{}", info);
             }
+        } else if let Some(Voting::Delegating {
+            balance,
+            target,
+            conviction,
+            prior,
+        }) = &votes_data
+        {
+            account_println!("Track {}:", class);
+            print_prior_lock(prior);
+            account_println!(
+                "Delegating {:.10} {symbol} to {} with {}x conviction",
+                plancks_to_units(*balance as f64),
+                format_address(target),
+                conviction,
+                symbol = chain::current().symbol
+            );
         }
-}
     }
 
     Ok(())
 }
-*/
 async fn fetch_account_balance(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    decoding: Decoding,
     key: &utils::AccountId32,
-) -> Result<
-    Option<polkadot::runtime_types::pallet_balances::types::AccountData<u128>>,
-    Box<subxt::Error>,
-> {
-    let storage_query = polkadot::storage().balances().account(key);
-
-    match api.storage().at_latest().await?.fetch(&storage_query).await {
-        Ok(Some(value)) => {
-            println!("[balances.account] {:?}", value);
-            Ok(Some(value))
-        }
-        Ok(None) => Ok(None),
-        Err(e) => {
-            eprintln!("[Error] Fetching failed for account balance: {}", e);
-            Err(Box::new(e))
-        }
-    }
+) -> Result<Option<AccountData>, Box<dyn std::error::Error>> {
+    // Relay chains keep balances in the account's `System::Account` entry rather than in
+    // `Balances::Account`
+    let storage_query = polkadot::storage().system().account(key);
+    let keys = vec![subxt::dynamic::Value::from_bytes(key)];
+    fetch_decoded(
+        api,
+        decoding,
+        &storage_query,
+        keys,
+        |info| AccountData {
+            free: info.data.free,
+            reserved: info.data.reserved,
+            frozen: info.data.frozen,
+        },
+        decode::account_data,
+    )
+    .await
 }
 
 async fn fetch_account_locks(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    decoding: Decoding,
     key: &utils::AccountId32,
-) -> Result<
-    Option<
-        polkadot::runtime_types::bounded_collections::weak_bounded_vec::WeakBoundedVec<
-            polkadot::runtime_types::pallet_balances::types::BalanceLock<u128>,
-        >,
-    >,
-    Box<subxt::Error>,
-> {
+) -> Result<Option<Vec<BalanceLock>>, Box<dyn std::error::Error>> {
     let storage_query = polkadot::storage().balances().locks(key);
+    let keys = vec![subxt::dynamic::Value::from_bytes(key)];
+    fetch_decoded(
+        api,
+        decoding,
+        &storage_query,
+        keys,
+        |locks| {
+            locks
+                .0
+                .into_iter()
+                .map(|lock| BalanceLock {
+                    id: lock.id,
+                    amount: lock.amount,
+                })
+                .collect()
+        },
+        |value| Ok(decode::balance_locks(value)),
+    )
+    .await
+}
 
-    match api.storage().at_latest().await?.fetch(&storage_query).await {
-        Ok(Some(value)) => {
-            //    println!("[balances.lock] {:?}", value);
-            Ok(Some(value))
-        }
+/// Reads a storage entry against the connected chain's metadata rather than the bundled
+/// one. Entries the chain doesn't have read as empty.
+async fn fetch_dynamic(
+    api: &Snapshot,
+    pallet: &str,
+    entry: &str,
+    keys: Vec<subxt::dynamic::Value>,
+) -> Result<Option<subxt::dynamic::DecodedValue>, Box<subxt::Error>> {
+    let storage_query = subxt::dynamic::storage(pallet, entry, keys);
+    let item = format!("{}::{}", pallet, entry);
+
+    match decode::missing_as_none(&item, api.fetch(&storage_query).await) {
+        Ok(Some(value)) => Ok(Some(value.to_value()?)),
         Ok(None) => Ok(None),
         Err(e) => {
-            eprintln!("[Error] Fetching failed for conviction votes: {}", e);
-            Err(Box::new(e))
+            eprintln!("[Error] Fetching failed for {}: {}", item, e);
+            Err(e)
+        }
+    }
+}
+
+/// Reads a storage entry with the generated types, or dynamically with `keys` if they
+/// don't match the chain or `decoding` asks for it. Either way the value is converted to
+/// the report's own type. Entries the chain doesn't have read as empty.
+async fn fetch_decoded<Address, T>(
+    api: &Snapshot,
+    decoding: Decoding,
+    address: &Address,
+    keys: Vec<subxt::dynamic::Value>,
+    from_static: impl FnOnce(Address::Target) -> T,
+    from_dynamic: impl FnOnce(&subxt::dynamic::DecodedValue) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<Option<T>, Box<dyn std::error::Error>>
+where
+    Address: StorageAddress<IsFetchable = Yes>,
+{
+    let (pallet, entry) = (address.pallet_name(), address.entry_name());
+    let item = format!("{}::{}", pallet, entry);
+    if decoding.tries_static() {
+        match decode::missing_as_none(&item, api.fetch(address).await) {
+            Ok(value) => return Ok(value.map(from_static)),
+            Err(e) if decoding == Decoding::Auto && decode::is_metadata_mismatch(&e) => {
+                decode::note_fallback(&item, &e)
+            }
+            Err(e) => {
+                eprintln!("[Error] Fetching failed for {}: {}", item, e);
+                return Err(e.into());
+            }
         }
     }
+
+    fetch_dynamic(api, pallet, entry, keys)
+        .await?
+        .map(|value| from_dynamic(&value))
+        .transpose()
+}
+
+/// Reads a runtime constant with the generated types, or dynamically if they don't match
+/// the chain or `decoding` asks for it. Constants the chain doesn't have read as `None`.
+fn constant_decoded<Address, T>(
+    api: &Snapshot,
+    decoding: Decoding,
+    address: &Address,
+    from_static: impl FnOnce(Address::Target) -> T,
+    from_dynamic: impl FnOnce(&subxt::dynamic::DecodedValue) -> Option<T>,
+) -> Result<Option<T>, Box<dyn std::error::Error>>
+where
+    Address: ConstantAddress,
+{
+    let (pallet, name) = (address.pallet_name(), address.constant_name());
+    let item = format!("{}::{}", pallet, name);
+    if decoding.tries_static() {
+        match decode::missing_as_none(&item, api.constants().at(address).map(Some)) {
+            Ok(value) => return Ok(value.map(from_static)),
+            Err(e) if decoding == Decoding::Auto && decode::is_metadata_mismatch(&e) => {
+                decode::note_fallback(&item, &e)
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let address = subxt::dynamic::constant(pallet, name);
+    let Some(value) = decode::missing_as_none(&item, api.constants().at(&address).map(Some))?
+    else {
+        return Ok(None);
+    };
+    from_dynamic(&value.to_value()?)
+        .map(Some)
+        .ok_or_else(|| format!("Unexpected shape of {}", item).into())
+}
+
+/// Reads a numeric runtime constant, such as a duration in blocks or eras.
+fn constant_number<Address, N>(
+    api: &Snapshot,
+    decoding: Decoding,
+    address: &Address,
+) -> Result<Option<N>, Box<dyn std::error::Error>>
+where
+    Address: ConstantAddress<Target = N>,
+    N: TryFrom<u128>,
+{
+    constant_decoded(
+        api,
+        decoding,
+        address,
+        |n| n,
+        |value| value.as_u128().and_then(|n| N::try_from(n).ok()),
+    )
+}
+
+/// Reads the account's holds or freezes as pallet, reason and amount. Their IDs are the
+/// runtime's own reason enums, which the generated types don't know, so they are always
+/// decoded against the connected chain's metadata.
+async fn fetch_encumbrances(
+    api: &Snapshot,
+    key: &utils::AccountId32,
+    kind: EncumbranceKind,
+) -> Result<Vec<Encumbrance>, Box<dyn std::error::Error>> {
+    let entry = match kind {
+        EncumbranceKind::Hold => "Holds",
+        _ => "Freezes",
+    };
+
+    let keys = vec![subxt::dynamic::Value::from_bytes(key)];
+    let value = fetch_dynamic(api, "Balances", entry, keys).await?;
+    Ok(value
+        .map(|value| decode::reasons(&value))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(pallet, reason, amount)| {
+            Encumbrance::new(kind, pallet, reason, plancks_to_units(amount as f64))
+        })
+        .collect())
 }
 
 async fn fetch_voting(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    decoding: Decoding,
     key: &utils::AccountId32,
     lock_class: u16,
-) -> Result<
-    Option<
-        polkadot::runtime_types::pallet_conviction_voting::vote::Voting<
-            u128,
-            utils::AccountId32,
-            u32,
-            u32,
-        >,
-    >,
-    Box<subxt::Error>,
-> {
+) -> Result<Option<Voting>, Box<dyn std::error::Error>> {
     let storage_query = polkadot::storage()
         .conviction_voting()
         .voting_for(key, lock_class);
-
-    match api.storage().at_latest().await?.fetch(&storage_query).await {
-        Ok(Some(value)) => {
-            //println!("[conviction_voting.voting_for] {:?}", value);
-            Ok(Some(value))
-        }
-        Ok(None) => Ok(None),
-        Err(e) => {
-            eprintln!("[Error] Fetching failed for conviction votes: {}", e);
-            Err(Box::new(e))
-        }
-    }
+    let keys = vec![
+        subxt::dynamic::Value::from_bytes(key),
+        subxt::dynamic::Value::u128(lock_class as u128),
+    ];
+    fetch_decoded(
+        api,
+        decoding,
+        &storage_query,
+        keys,
+        voting_from_static,
+        decode::voting,
+    )
+    .await
 }
 
 async fn fetch_class_locks(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    decoding: Decoding,
     key: &utils::AccountId32,
-) -> Result<
-    Option<polkadot::runtime_types::bounded_collections::bounded_vec::BoundedVec<(u16, u128)>>,
-    Box<subxt::Error>,
-> {
+) -> Result<Option<Vec<(u16, u128)>>, Box<dyn std::error::Error>> {
     let storage_query = polkadot::storage().conviction_voting().class_locks_for(key);
+    let keys = vec![subxt::dynamic::Value::from_bytes(key)];
+    fetch_decoded(
+        api,
+        decoding,
+        &storage_query,
+        keys,
+        |locks| locks.0,
+        |value| Ok(decode::class_locks(value)),
+    )
+    .await
+}
 
-    match api.storage().at_latest().await?.fetch(&storage_query).await {
-        Ok(Some(value)) => {
-            //println!("[Class locks data] {:?}", value);
-            Ok(Some(value))
-        }
-        Ok(None) => Ok(None),
-        Err(e) => {
-            eprintln!("[Error] Fetching failed for class locks: {}", e);
-            Err(Box::new(e))
-        }
-    }
+/// Reads referenda concurrently, in the order given.
+async fn fetch_referenda(
+    api: &Snapshot,
+    decoding: Decoding,
+    ref_nums: impl Iterator<Item = u32>,
+) -> Result<Vec<Option<Referendum>>, Box<dyn std::error::Error>> {
+    futures::stream::iter(ref_nums)
+        .map(|ref_num| fetch_referendum_info(api, decoding, ref_num))
+        .buffered(api.parallelism())
+        .try_collect()
+        .await
 }
 
+/// Reads a referendum, once per run however many accounts voted on it.
 async fn fetch_referendum_info(
-    api: &OnlineClient<PolkadotConfig>,
-    key: &utils::AccountId32,
+    api: &Snapshot,
+    decoding: Decoding,
     ref_num: u32,
-) -> Result<
-    Option<
-        polkadot::runtime_types::pallet_referenda::types::ReferendumInfo<
-            u16,
-            polkadot::runtime_types::polkadot_runtime::OriginCaller,
-            u32,
-            polkadot::runtime_types::frame_support::traits::preimages::Bounded<
-                polkadot::runtime_types::polkadot_runtime::RuntimeCall,
-            >,
-            u128,
-            polkadot::runtime_types::pallet_conviction_voting::types::Tally<u128>,
-            utils::AccountId32,
-            (u32, u32),
-        >,
-    >,
-    Box<subxt::Error>,
-> {
+) -> Result<Option<Referendum>, Box<dyn std::error::Error>> {
+    api.referenda()
+        .get_or_fetch(ref_num, || {
+            fetch_referendum_info_uncached(api, decoding, ref_num)
+        })
+        .await
+}
+
+/// Reads a referendum with the generated types, or dynamically if they don't match the
+/// chain or `decoding` asks for it.
+async fn fetch_referendum_info_uncached(
+    api: &Snapshot,
+    decoding: Decoding,
+    ref_num: u32,
+) -> Result<Option<Referendum>, Box<dyn std::error::Error>> {
     let storage_query = polkadot::storage().referenda().referendum_info_for(ref_num);
+    let keys = vec![subxt::dynamic::Value::u128(ref_num as u128)];
+    fetch_decoded(
+        api,
+        decoding,
+        &storage_query,
+        keys,
+        referendum_from_static,
+        decode::referendum,
+    )
+    .await
+}
 
-    match api.storage().at_latest().await?.fetch(&storage_query).await {
-        Ok(Some(value)) => {
-            //    println!("[Referendum Data] {:?}", value);
-            Ok(Some(value))
-        }
-        Ok(None) => Ok(None),
-        Err(e) => {
-            eprintln!("[Error] Fetching failed for referenda: {}", e);
-            Err(Box::new(e))
-        }
+async fn fetch_bonded(
+    api: &Snapshot,
+    decoding: Decoding,
+    key: &utils::AccountId32,
+) -> Result<Option<utils::AccountId32>, Box<dyn std::error::Error>> {
+    let storage_query = polkadot::storage().staking().bonded(key);
+    let keys = vec![subxt::dynamic::Value::from_bytes(key)];
+    fetch_decoded(
+        api,
+        decoding,
+        &storage_query,
+        keys,
+        |controller| controller,
+        |value| decode::account_id(value).ok_or_else(|| "Staking::Bonded is not an account".into()),
+    )
+    .await
+}
+
+async fn fetch_staking_ledger(
+    api: &Snapshot,
+    decoding: Decoding,
+    controller: &utils::AccountId32,
+) -> Result<Option<StakingLedger>, Box<dyn std::error::Error>> {
+    let storage_query = polkadot::storage().staking().ledger(controller);
+    let keys = vec![subxt::dynamic::Value::from_bytes(controller)];
+    fetch_decoded(
+        api,
+        decoding,
+        &storage_query,
+        keys,
+        |ledger| StakingLedger {
+            total: ledger.total,
+            active: ledger.active,
+            unlocking: ledger
+                .unlocking
+                .0
+                .into_iter()
+                .map(|chunk| UnlockChunk {
+                    value: chunk.value,
+                    era: chunk.era,
+                })
+                .collect(),
+        },
+        decode::staking_ledger,
+    )
+    .await
+}
+
+async fn fetch_era_timings(
+    api: &Snapshot,
+    decoding: Decoding,
+) -> Result<Option<EraTimings>, Box<dyn std::error::Error>> {
+    let storage_query = polkadot::storage().staking().active_era();
+    let active_era = fetch_decoded(
+        api,
+        decoding,
+        &storage_query,
+        vec![],
+        |era| ActiveEra {
+            index: era.index,
+            start: era.start,
+        },
+        decode::active_era,
+    )
+    .await?;
+    let Some(active_era) = active_era else {
+        return Ok(None);
+    };
+
+    let staking = polkadot::constants().staking();
+    let babe = polkadot::constants().babe();
+    let bonding_duration = constant_number(api, decoding, &staking.bonding_duration())?;
+    let sessions_per_era = constant_number(api, decoding, &staking.sessions_per_era())?;
+    let epoch_duration = constant_number(api, decoding, &babe.epoch_duration())?;
+    // Chains without BABE have no epochs to count eras in
+    let (Some(bonding_duration), Some(sessions_per_era), Some(epoch_duration)) =
+        (bonding_duration, sessions_per_era, epoch_duration)
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(EraTimings {
+        active_era: active_era.index,
+        active_era_start: active_era
+            .start
+            .and_then(|millis| Utc.timestamp_millis_opt(millis as i64).single()),
+        bonding_duration,
+        era_length: (epoch_duration as u32).saturating_mul(sessions_per_era),
+    }))
+}
+
+/// Turns the account's staking ledger into locked intervals: one per unbonding chunk, and
+/// one for the active stake, which stays locked for a full bonding duration after it is
+/// unbonded.
+async fn process_staking(
+    api: &Snapshot,
+    clock: &BlockClock,
+    decoding: Decoding,
+    key: &utils::AccountId32,
+) -> Result<Vec<LockedInterval>, Box<dyn std::error::Error>> {
+    let mut locked_intervals = Vec::new();
+
+    // The account may be a stash with a separate controller, or the controller itself
+    let controller = fetch_bonded(api, decoding, key)
+        .await?
+        .unwrap_or_else(|| key.clone());
+    let Some(ledger) = fetch_staking_ledger(api, decoding, &controller).await? else {
+        return Ok(locked_intervals);
+    };
+    let Some(era_timings) = fetch_era_timings(api, decoding).await? else {
+        return Ok(locked_intervals);
+    };
+
+    account_println!(
+        "Staking ledger: Total: {:.10} {symbol}, Active: {:.10} {symbol}, Active era: {}",
+        plancks_to_units(ledger.total as f64),
+        plancks_to_units(ledger.active as f64),
+        era_timings.active_era,
+        symbol = chain::current().symbol
+    );
+
+    let era_block = |era: u32| {
+        era_timings.era_start_block(
+            era,
+            clock.current_block(),
+            clock.current_time(),
+            clock.ms_per_block(),
+        )
+    };
+
+    for chunk in ledger.unlocking.iter() {
+        let status = if chunk.era <= era_timings.active_era {
+            LockStatus::Unlockable
+        } else {
+            LockStatus::LockedUntil {
+                block: era_block(chunk.era),
+            }
+        };
+        let (start_date, end) = calculate_end_datetime(clock, &status).await?;
+        account_println!(
+            "Unbonding chunk: {:.10} {symbol}, Era: {}, Unlocks: {}{}",
+            plancks_to_units(chunk.value as f64),
+            chunk.era,
+            end.date.format("%Y-%m-%d %H:%M:%S"),
+            format_margin(&end),
+            symbol = chain::current().symbol
+        );
+
+        locked_intervals.push(LockedInterval {
+            start_date,
+            end_date: end.date,
+            end_date_margin_secs: end.margin_secs,
+            release: Release::AtEnd,
+            amount: plancks_to_units(chunk.value as f64),
+            plancks: chunk.value,
+            status,
+            source: LockSource::StakingUnbonding { era: chunk.era },
+        });
+    }
+
+    if ledger.active > 0 {
+        let status = LockStatus::Bonded {
+            earliest_unlock: era_block(era_timings.unbond_now_era()),
+        };
+        let (start_date, end) = calculate_end_datetime(clock, &status).await?;
+
+        locked_intervals.push(LockedInterval {
+            start_date,
+            end_date: end.date,
+            end_date_margin_secs: end.margin_secs,
+            release: Release::AtEnd,
+            amount: plancks_to_units(ledger.active as f64),
+            plancks: ledger.active,
+            status,
+            source: LockSource::StakingBonded,
+        });
     }
+
+    Ok(locked_intervals)
 }
 
 async fn fetch_vesting(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    decoding: Decoding,
     key: &utils::AccountId32,
-) -> Result<
-    Option<
-        polkadot::runtime_types::bounded_collections::bounded_vec::BoundedVec<
-            polkadot::runtime_types::pallet_vesting::vesting_info::VestingInfo<u128, u32>,
-        >,
-    >,
-    Box<subxt::Error>,
-> {
+) -> Result<Option<Vec<VestingInfo>>, Box<dyn std::error::Error>> {
     let storage_query = polkadot::storage().vesting().vesting(key);
-
-    match api.storage().at_latest().await?.fetch(&storage_query).await {
-        Ok(Some(value)) => {
-            //println!("[Vesting Data] {:?}", value);
-            Ok(Some(value))
-        }
-        Ok(None) => {
-            // If no vesting data is found, simply return None instead of an error
-            Ok(None)
-        }
-        Err(e) => {
-            eprintln!("[Error] Fetching failed for vesting: {}", e);
-            Err(Box::new(e))
-        }
-    }
+    let keys = vec![subxt::dynamic::Value::from_bytes(key)];
+    fetch_decoded(
+        api,
+        decoding,
+        &storage_query,
+        keys,
+        |schedules| {
+            schedules
+                .0
+                .into_iter()
+                .map(|info| VestingInfo {
+                    locked: info.locked,
+                    per_block: info.per_block,
+                    starting_block: info.starting_block,
+                })
+                .collect()
+        },
+        |value| Ok(decode::vesting(value)),
+    )
+    .await
 }
 
-fn calculate_vesting_datetimes(
+async fn calculate_vesting_datetimes(
+    clock: &BlockClock,
     starting_block: u32,
     total_blocks_until_vested: u32,
-    current_block: u32,
-) -> (DateTime<Utc>, DateTime<Utc>) {
-    let base_datetime = if starting_block < GENESIS_THRESHOLD {
-        // Use the datetime for block 1 if within the threshold
-        NaiveDate::from_ymd(2020, 5, 26)
-            .and_hms(15, 36, 18)
-            .and_utc()
-    } else {
-        // Use the original hardcoded datetime for later blocks
-        NaiveDate::from_ymd(2023, 8, 25).and_hms(13, 1, 0).and_utc()
-    };
-
-    // Calculate difference in minutes between base_datetime and starting_block
-    let minutes_diff_start = (starting_block as i64) * SECONDS_PER_BLOCK / MINUTES_PER_HOUR;
-    let start_datetime = base_datetime + Duration::minutes(minutes_diff_start);
-
-    // Calculate end datetime
-    let minutes_diff_end =
-        (total_blocks_until_vested) as i64 * SECONDS_PER_BLOCK / MINUTES_PER_HOUR;
-    let end_datetime = start_datetime + Duration::minutes(minutes_diff_end);
-
-    (start_datetime, end_datetime)
+) -> Result<(BlockTime, BlockTime), Box<dyn std::error::Error>> {
+    let start = clock.block_time(starting_block).await?;
+    let end = clock
+        .block_time(starting_block.saturating_add(total_blocks_until_vested))
+        .await?;
+    Ok((start, end))
 }
+
+/// Prints the account's vesting schedules and turns them into linearly releasing locked
+/// intervals.
 async fn display_vesting_info(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    clock: &BlockClock,
+    decoding: Decoding,
     key: &utils::AccountId32,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let vesting_data_opt = fetch_vesting(api, key).await?;
+    locks: Option<&[BalanceLock]>,
+) -> Result<(VestingSummary, Vec<LockedInterval>), Box<dyn std::error::Error>> {
+    let mut summary = VestingSummary::default();
+    let mut locked_intervals = Vec::new();
+    let vesting_data_opt = fetch_vesting(api, decoding, key).await?;
 
     // If there's no vesting data, exit early
-    if let Some(vesting_data) = vesting_data_opt {
-        let mut blocks_sub = api.blocks().subscribe_finalized().await?;
-
-        match blocks_sub.next().await {
-            Some(block) => {
-                let block = block?;
-                let current_block_number = block.header().number;
-
-                println!("Detailed Vesting Schedule:");
-
-                for vesting_info in vesting_data.0.iter() {
-                    let total_blocks_until_vested =
-                        vesting_info.locked / vesting_info.per_block as u128;
-                    let (start_date, end_date) = calculate_vesting_datetimes(
-                        vesting_info.starting_block,
-                        total_blocks_until_vested as u32,
-                        current_block_number,
-                    );
+    let Some(vesting_data) = vesting_data_opt else {
+        account_println!("No vesting data available for the account.");
+        return Ok((summary, locked_intervals));
+    };
 
-                    let locked_in_dot = vesting_info.locked as f64 / PLANCKS_PER_DOT;
-                    let per_block_in_dot = vesting_info.per_block as f64 / PLANCKS_PER_DOT;
+    let current_block = clock.current_block();
+    account_println!("Detailed Vesting Schedule:");
 
-                    println!(
-                        "Start Date: {}, Locked: {:.10} DOT, Per Block: {:.10} DOT, End Date: {}",
-                        start_date.format("%Y-%m-%d %H:%M:%S"),
-                        locked_in_dot,
-                        per_block_in_dot,
-                        end_date.format("%Y-%m-%d %H:%M:%S")
-                    );
-                }
-            }
-            None => {
-                println!("No block data available.");
-            }
+    let mut still_locked_total: u128 = 0;
+    for vesting_info in vesting_data.iter() {
+        let ending_block = vesting::ending_block(
+            vesting_info.locked,
+            vesting_info.per_block,
+            vesting_info.starting_block,
+        );
+        let (start, end) = calculate_vesting_datetimes(
+            clock,
+            vesting_info.starting_block,
+            ending_block - vesting_info.starting_block,
+        )
+        .await?;
+        let still_locked = vesting::locked_at(
+            vesting_info.locked,
+            vesting_info.per_block,
+            vesting_info.starting_block,
+            current_block,
+        );
+        still_locked_total = still_locked_total.saturating_add(still_locked);
+
+        let locked_in_units = plancks_to_units(vesting_info.locked as f64);
+        let per_block_in_units = plancks_to_units(vesting_info.per_block as f64);
+
+        account_println!(
+            "Start Date: {}, Locked: {:.10} {symbol}, Per Block: {:.10} {symbol}, Still Locked: {:.10} {symbol}, End Date: {}{}",
+            start.date.format("%Y-%m-%d %H:%M:%S"),
+            locked_in_units,
+            per_block_in_units,
+            plancks_to_units(still_locked as f64),
+            end.date.format("%Y-%m-%d %H:%M:%S"),
+            format_margin(&end),
+            symbol = chain::current().symbol
+        );
+
+        summary.schedules.push(VestingSchedule {
+            locked: locked_in_units,
+            per_block: per_block_in_units,
+            starting_block: vesting_info.starting_block,
+            ending_block,
+            start_date: start.date,
+            end_date: end.date,
+            unlocked_to_date: plancks_to_units((vesting_info.locked - still_locked) as f64),
+            still_locked: plancks_to_units(still_locked as f64),
+        });
+
+        if still_locked > 0 {
+            locked_intervals.push(LockedInterval {
+                start_date: clock.current_time(),
+                end_date: end.date,
+                end_date_margin_secs: end.margin_secs,
+                release: Release::Linear {
+                    from: start.date.max(clock.current_time()),
+                },
+                amount: plancks_to_units(still_locked as f64),
+                plancks: still_locked,
+                status: LockStatus::LockedUntil {
+                    block: ending_block,
+                },
+                source: LockSource::Vesting {
+                    starting_block: vesting_info.starting_block,
+                },
+            });
         }
-    } else {
-        println!("No vesting data available for the account.");
     }
 
-    Ok(())
+    // The vesting lock only shrinks when `vest()` is called, so anything it holds beyond
+    // what the schedules still lock has vested and can be claimed
+    let vesting_lock = locks
+        .and_then(|locks| {
+            locks
+                .iter()
+                .find(|lock| &lock.id == b"vesting ")
+                .map(|lock| lock.amount)
+        })
+        .unwrap_or(0);
+    let claimable = vesting_lock.saturating_sub(still_locked_total);
+    if claimable > 0 {
+        summary.claimable = plancks_to_units(claimable as f64);
+        account_println!(
+            "Claimable with vest(): {:.10} {}",
+            summary.claimable,
+            chain::current().symbol
+        );
+    }
+
+    Ok((summary, locked_intervals))
+}
+
+/// Describes how far off an estimated date may be, for appending to a printed date.
+fn format_margin(time: &BlockTime) -> String {
+    if !time.estimated {
+        return String::new();
+    }
+    let hours = time.margin_secs as f64 / 3600.0;
+    format!(" (estimated, ±{:.1}h)", hours)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let api = connect_to_polkadot_node().await?;
-    let addresses = match read_addresses_from_input() {
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
+    let addresses = match cli.command.address_args().resolve() {
         Ok(addrs) => {
             for address in &addrs {
                 println!("{}", address);
@@ -709,83 +1540,349 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         Err(e) => {
             println!("Error: {}", e);
-            return Err(e.into());
+            return Err(e);
         }
     };
+    let decoding = cli.decoding(&config);
+    let ladder = liquidity::ladder(&cli.ladder_days(&config))?;
+    let (chain_name, profile) = cli.chain_profile(&config)?;
+    let cache = cli.cache(&config);
+    if cli.offline {
+        let Commands::Report(args) = &cli.command else {
+            return Err("--offline only works with the report subcommand".into());
+        };
+        let mut all_data = cache.load_report(&chain_name, &addresses)?;
+        let chain = Chain::from_report(&all_data, profile)?;
+        let now = Utc::now();
+        println!(
+            "[Cache] OFFLINE: balances and locks of {} as of block {}, authored {} UTC, \
+             counted from {} UTC",
+            chain_name,
+            all_data["snapshot"]["block"],
+            all_data["snapshot"]["date"]
+                .as_str()
+                .unwrap_or("at an unknown time"),
+            now.format("%Y-%m-%d %H:%M:%S")
+        );
+        for account in all_data["accounts"].as_array().into_iter().flatten() {
+            if let Some(snapshot) = account.get("snapshot") {
+                println!(
+                    "[Cache] OFFLINE: {} as of block {}, authored {} UTC",
+                    account["address"].as_str().unwrap_or_default(),
+                    snapshot["block"],
+                    snapshot["date"].as_str().unwrap_or("at an unknown time")
+                );
+            }
+        }
+        refresh_report(&mut all_data, &chain, now, &ladder)?;
+        return write_report(&all_data, args);
+    }
+
+    let mut node = NodeConnection::connect(cli.endpoints(&config, &profile)).await?;
+    let mut snapshot = match (cli.at_block, cli.at_date) {
+        (Some(block), _) => Snapshot::at_block(&node, block).await?,
+        (None, Some(date)) => Snapshot::at_end_of(&node, decoding, date).await?,
+        (None, None) => Snapshot::finalized(&node).await?,
+    }
+    .with_parallelism(cli.parallelism(&config))
+    .with_referendum_store(cache.referenda(node.api().genesis_hash()));
+    let chain = Chain::load(&chain_name, profile, snapshot.client(), node.rpc())
+        .await?
+        .install()?;
+    let mut clock = BlockClock::new(
+        node.api().clone(),
+        node.rpc().clone(),
+        decoding,
+        snapshot.block(),
+        chain.block_time_ms,
+    )
+    .await?;
+
     let mut all_data = json!({
+        "schema_version": REPORT_SCHEMA_VERSION,
         "date": Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        "chain": {
+            "name": chain.name,
+            "symbol": chain.symbol,
+            "decimals": chain.decimals,
+            "ss58_prefix": chain.ss58_prefix,
+        },
+        "snapshot": {
+            "block": snapshot.block(),
+            "hash": format!("{:?}", snapshot.hash()),
+            "date": clock.current_time().format("%Y-%m-%d %H:%M:%S").to_string(),
+        },
+        "clock": {
+            "block": clock.current_block(),
+            "seconds_per_block": format!("{:.3}", clock.ms_per_block() / 1000.0),
+        },
+        // Ladder buckets as the report shows them, longest first
+        "ladder": ladder.iter().rev().collect::<Vec<_>>(),
         "accounts": []
     });
 
-    for address in &addresses {
-        let data = process_address(&api, address).await?;
-        all_data["accounts"].as_array_mut().unwrap().push(data);
+    // Terminal subcommands print as they go, so only reports process accounts side by side
+    let account_parallelism = match cli.command {
+        Commands::Report(_) => snapshot.parallelism(),
+        _ => 1,
+    };
+    let mut results: Vec<Option<JsonValue>> = vec![None; addresses.len()];
+    let mut pending: Vec<usize> = (0..addresses.len()).collect();
+    let mut attempts = 0;
+    while !pending.is_empty() {
+        let outcomes: Vec<_> = futures::stream::iter(pending)
+            .map(|index| {
+                let (command, snapshot, clock) = (&cli.command, &snapshot, &clock);
+                let (ladder, address) = (&ladder, &addresses[index]);
+                let output = RefCell::new(String::new());
+                ACCOUNT_OUTPUT.scope(output, async move {
+                    let result = run_command(command, snapshot, clock, decoding, ladder, address);
+                    let result = result.await;
+                    (index, result, ACCOUNT_OUTPUT.with(RefCell::take))
+                })
+            })
+            .buffered(account_parallelism)
+            .map(|(index, result, output)| {
+                print!("{}", output);
+                (index, result)
+            })
+            .collect()
+            .await;
+
+        // Retry addresses on the next endpoint if the connection drops part way through
+        pending = Vec::new();
+        let mut connection_error = None;
+        for (index, result) in outcomes {
+            match result {
+                Ok(data) => results[index] = data,
+                Err(e)
+                    if connection::is_connection_error(e.as_ref())
+                        && attempts < node.endpoint_count() =>
+                {
+                    pending.push(index);
+                    connection_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if let Some(e) = connection_error {
+            eprintln!("[Error] Connection to '{}' failed: {}", node.url(), e);
+            attempts += 1;
+            node.failover().await?;
+            snapshot.reconnect(&node)?;
+            clock.reconnect(node.api().clone(), node.rpc().clone());
+        }
+    }
+    all_data["accounts"] = JsonValue::Array(results.into_iter().flatten().collect());
+
+    // A cache that can't be written only costs the next run some time
+    if let Err(e) = snapshot.referenda().save() {
+        eprintln!("[Cache] Failed to save referenda: {}", e);
+    }
+    if let Commands::Report(args) = &cli.command {
+        if let Err(e) = cache.save_report(&chain_name, node.api().genesis_hash(), &all_data) {
+            eprintln!("[Cache] Failed to save the report: {}", e);
+        }
+        write_report(&all_data, args)?;
     }
-    generate_html_for_all_addresses(&all_data)?;
 
     println!("\n[Completion] Finished processing all addresses.");
     Ok(())
 }
 
-async fn connect_to_polkadot_node(
-) -> Result<OnlineClient<PolkadotConfig>, Box<dyn std::error::Error>> {
-    println!("[Connection] Attempting to connect to 'wss://rpc.polkadot.io:443'...");
-    OnlineClient::<PolkadotConfig>::from_url("wss://rpc.polkadot.io:443")
-        .await
-        .map_err(Into::into)
-}
+/// Runs the subcommand for a single address. Only `report` produces data; the other
+/// subcommands print their results as they go.
+async fn run_command(
+    command: &Commands,
+    api: &Snapshot,
+    clock: &BlockClock,
+    decoding: Decoding,
+    ladder: &[Bucket],
+    address: &str,
+) -> Result<Option<JsonValue>, Box<dyn std::error::Error>> {
+    match command {
+        Commands::Report(_) => {
+            return process_address(api, clock, decoding, ladder, address)
+                .await
+                .map(Some)
+        }
+        Commands::Locks(_) => {
+            let key = parse_address(address)?;
+            let locks = fetch_account_locks(api, decoding, &key).await?;
+            display_balances(api, decoding, &key, locks.as_deref()).await?;
+            display_lock_totals(locks.as_deref());
+            display_class_locks(api, decoding, &key).await?;
+            process_staking(api, clock, decoding, &key).await?;
+        }
+        Commands::Vesting(_) => {
+            let key = parse_address(address)?;
+            let locks = fetch_account_locks(api, decoding, &key).await?;
+            display_vesting_info(api, clock, decoding, &key, locks.as_deref()).await?;
+        }
+        Commands::Referenda(_) => {
+            let key = parse_address(address)?;
+            gather_detailed_vote_info(api, decoding, &key).await?;
+        }
+    }
 
-fn read_addresses_from_file(path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    BufReader::new(File::open(path)?)
-        .lines()
-        .collect::<Result<_, _>>()
-        .map_err(Into::into)
+    Ok(None)
 }
 
-fn read_addresses_from_input() -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let apple_script = r#"
-    set defaultText to "
-
-
-
-
-
-
-
-
-    "
-    set userInput to text returned of (display dialog "Please input addresses, separated by newlines:" default answer defaultText)
-    return userInput
-    "#;
-
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(apple_script)
-        .output()?;
-
-    let user_input = String::from_utf8(output.stdout)?;
+/// Formats an account as an SS58 address with the chain's prefix.
+fn format_address(account: &utils::AccountId32) -> String {
+    sp_core::crypto::AccountId32::from(account.0)
+        .to_ss58check_with_version(Ss58AddressFormat::custom(chain::current().ss58_prefix))
+}
 
-    // Split the input by newline, filter out any empty lines, and collect into a Vec<String>
-    let addresses = user_input.lines().filter(|s| !s.trim().is_empty()).map(|s| s.to_string()).collect();
-    Ok(addresses)
+fn parse_address(address: &str) -> Result<utils::AccountId32, Box<dyn std::error::Error>> {
+    account_println!("\n[Processing] Address: {}", address);
+    utils::AccountId32::from_str(address)
+        .map_err(|e| format!("Invalid address {}: {}", address, e).into())
 }
+
 async fn process_address(
-    api: &OnlineClient<PolkadotConfig>,
+    api: &Snapshot,
+    clock: &BlockClock,
+    decoding: Decoding,
+    ladder: &[Bucket],
     address: &str,
 ) -> Result<JsonValue, Box<dyn std::error::Error>> {
-    println!("\n[Processing] Address: {}", address);
-    let public_key_bytes = utils::AccountId32::from_str(address)?;
-
-    if let Err(e) = fetch_account_balance(&api, &public_key_bytes).await {
-        eprintln!("[Error] Failed to fetch balance: {}", e);
-    }
-    if let Err(e) = fetch_account_locks(&api, &public_key_bytes).await {
-        eprintln!("[Error] Failed to fetch locked balance: {}", e);
-    }
-    let xr_data = gather_and_cross_reference(&api, &public_key_bytes).await?;
+    let public_key_bytes = parse_address(address)?;
+    let xr_data =
+        gather_and_cross_reference(api, clock, decoding, ladder, &public_key_bytes).await?;
 
     Ok(json!({
         "address": address,
         "data": xr_data,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use subxt::error::MetadataError;
+
+    fn polkadot() -> Chain {
+        Chain {
+            name: "polkadot".to_string(),
+            symbol: "DOT".to_string(),
+            decimals: 10,
+            ss58_prefix: 0,
+            block_time_ms: 6000.0,
+            vote_locking_period: None,
+        }
+    }
+
+    #[test]
+    fn accounts_render_on_chains_without_staking_or_vesting() {
+        let missing = |pallet: &str| {
+            subxt::Error::Metadata(MetadataError::PalletNameNotFound(pallet.to_string()))
+        };
+        let ledger: Option<StakingLedger> =
+            decode::missing_as_none("Staking::Ledger", Err(missing("Staking"))).unwrap();
+        let schedules: Option<Vec<VestingInfo>> =
+            decode::missing_as_none("Vesting::Vesting", Err(missing("Vesting"))).unwrap();
+        assert!(ledger.is_none() && schedules.is_none());
+
+        // With nothing read, the account has no locked intervals and empty sections
+        let sections = json!({
+            "locks": LockTotals::empty(),
+            "class_locks": [],
+            "balance": BalanceSummary::default(),
+            "encumbrances": [],
+            "vesting": VestingSummary::default(),
+        });
+        let ladder = liquidity::ladder(&[7, 30]).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let address = "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
+        let report = json!({
+            "chain": { "symbol": "DOT" },
+            "snapshot": { "block": 100, "date": "2024-01-01 00:00:00" },
+            "ladder": ladder.iter().rev().collect::<Vec<_>>(),
+            "accounts": [{
+                "address": address,
+                "data": account_report(&polkadot(), sections, &[], now, &ladder).unwrap(),
+            }],
+        });
+
+        let html = render_html(&report).unwrap();
+        assert!(html.contains(address));
+        assert!(html.contains("<th>Conviction Voting Lock</th>"));
+        assert!(table::markdown(&report).contains(address));
+    }
+
+    #[tokio::test]
+    async fn accounts_side_by_side_print_in_one_piece() {
+        let account = |name: &'static str, steps: u64| {
+            ACCOUNT_OUTPUT.scope(RefCell::new(String::new()), async move {
+                for step in 0..steps {
+                    account_println!("{} {}", name, step);
+                    tokio::task::yield_now().await;
+                }
+                ACCOUNT_OUTPUT.with(RefCell::take)
+            })
+        };
+        let (first, second) = tokio::join!(account("first", 3), account("second", 2));
+        assert_eq!(first, "first 0\nfirst 1\nfirst 2\n");
+        assert_eq!(second, "second 0\nsecond 1\n");
+    }
+
+    #[test]
+    fn offline_reports_count_from_now() {
+        let chain = polkadot();
+        let snapshot = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let interval = LockedInterval {
+            start_date: snapshot,
+            end_date: snapshot + chrono::Duration::days(20),
+            end_date_margin_secs: 0,
+            release: Release::AtEnd,
+            amount: 1.0,
+            plancks: 10_000_000_000,
+            status: LockStatus::LockedUntil { block: 288_100 },
+            source: LockSource::Vote {
+                referendum: 7,
+                track: 0,
+                referendum_state: "approved".to_string(),
+                vote: VoteKind::Standard {
+                    direction: "aye".to_string(),
+                    conviction: 1,
+                },
+            },
+        };
+        let cached_ladder = liquidity::ladder(&[7, 30]).unwrap();
+        let data =
+            account_report(&chain, json!({}), &[interval], snapshot, &cached_ladder).unwrap();
+        let cached = json!({
+            "chain": { "symbol": "DOT" },
+            "snapshot": { "block": 100, "date": "2024-01-01 00:00:00" },
+            "ladder": cached_ladder.iter().rev().collect::<Vec<_>>(),
+            "accounts": [{ "address": "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5", "data": data }],
+        });
+        let liquid_in = |report: &JsonValue| {
+            report["accounts"][0]["data"]["liquidity"]["locks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|bucket| bucket["amount"] != "none")
+                .map(|bucket| bucket["lock_category"].clone())
+                .unwrap_or_default()
+        };
+        assert_eq!(liquid_in(&cached), json!("Locked 8-30 Days"));
+
+        // Two weeks on, with a ladder configured since, the lock ends within days
+        let mut report = cached.clone();
+        let now = snapshot + chrono::Duration::days(14);
+        let ladder = liquidity::ladder(&[10, 60]).unwrap();
+        refresh_report(&mut report, &chain, now, &ladder).unwrap();
+        assert_eq!(liquid_in(&report), json!("Locked 1-10 Days"));
+        assert_eq!(report["ladder"].as_array().unwrap().len(), ladder.len());
+        assert_eq!(report["counted_from"], "2024-01-15 00:00:00");
+        assert_eq!(
+            report["accounts"][0]["data"]["intervals"],
+            cached["accounts"][0]["data"]["intervals"]
+        );
+        assert!(render_html(&report)
+            .unwrap()
+            .contains("counted from 2024-01-15 00:00:00 UTC"));
+    }
+}
@@ -0,0 +1,281 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+use crate::cache;
+use crate::locks::Referendum;
+
+/// How long an ongoing referendum read by an earlier run is reused for. Its tally and
+/// deciding status change from block to block, so only runs close together share it.
+const ONGOING_TTL_SECS: i64 = 10 * 60;
+
+/// Referenda read at one block. Accounts often vote on the same referenda, so lookups
+/// are shared between them, including ones still in flight. With a store, referenda read
+/// by earlier runs are reused too.
+#[derive(Default)]
+pub struct ReferendumCache {
+    block: u32,
+    store: Option<ReferendumStore>,
+    entries: Mutex<HashMap<u32, Arc<OnceCell<Option<Referendum>>>>>,
+}
+
+impl ReferendumCache {
+    /// A cache for lookups at `block`, backed by `store` across runs.
+    pub fn new(block: u32, store: ReferendumStore) -> Self {
+        ReferendumCache {
+            block,
+            store: Some(store),
+            entries: Mutex::default(),
+        }
+    }
+
+    /// The referendum from the cache, or from `fetch` if no one has read it yet. Failed
+    /// lookups aren't cached, so the next caller tries again.
+    pub async fn get_or_fetch<F, Fut>(
+        &self,
+        ref_num: u32,
+        fetch: F,
+    ) -> Result<Option<Referendum>, Box<dyn std::error::Error>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<Referendum>, Box<dyn std::error::Error>>>,
+    {
+        let cell = self
+            .entries
+            .lock()
+            .unwrap()
+            .entry(ref_num)
+            .or_default()
+            .clone();
+        cell.get_or_try_init(|| async {
+            let Some(store) = &self.store else {
+                return fetch().await;
+            };
+            if let Some(referendum) = store.get(ref_num, self.block, Utc::now()) {
+                return Ok(Some(referendum));
+            }
+            let referendum = fetch().await?;
+            if let Some(referendum) = &referendum {
+                store.insert(ref_num, referendum.clone(), self.block, Utc::now());
+            }
+            Ok(referendum)
+        })
+        .await
+        .cloned()
+    }
+
+    /// Writes referenda read during this run back to the store.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.store {
+            Some(store) => store.save(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A referendum read by an earlier run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredReferendum {
+    referendum: Referendum,
+    /// Block it was read at.
+    block: u32,
+    fetched_at: DateTime<Utc>,
+}
+
+impl StoredReferendum {
+    /// Whether this is still what the chain holds at `block`. Finished referenda hold from
+    /// their end block on; ongoing ones only at the block they were read at, or at later
+    /// blocks for a short while.
+    fn holds_at(&self, block: u32, now: DateTime<Utc>) -> bool {
+        match self.referendum.state.end() {
+            Some(end) => block >= end,
+            None => {
+                block == self.block
+                    || (block > self.block
+                        && now - self.fetched_at < Duration::seconds(ONGOING_TTL_SECS))
+            }
+        }
+    }
+}
+
+/// Referenda of one chain kept on disk between runs, keyed by referendum index.
+pub struct ReferendumStore {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<u32, StoredReferendum>>,
+}
+
+impl ReferendumStore {
+    /// Opens the store at `path`. A missing file is an empty store; an unreadable one is
+    /// reported and started over.
+    pub fn open(path: PathBuf) -> Self {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("[Cache] Ignoring unreadable {}: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        ReferendumStore {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn get(&self, ref_num: u32, block: u32, now: DateTime<Utc>) -> Option<Referendum> {
+        let entries = self.entries.lock().unwrap();
+        let stored = entries.get(&ref_num)?;
+        stored
+            .holds_at(block, now)
+            .then(|| stored.referendum.clone())
+    }
+
+    /// Stores a referendum read at `block`, unless a later reading is already stored.
+    fn insert(&self, ref_num: u32, referendum: Referendum, block: u32, now: DateTime<Utc>) {
+        let mut entries = self.entries.lock().unwrap();
+        let ongoing = referendum.state.end().is_none();
+        let superseded = entries
+            .get(&ref_num)
+            .is_some_and(|stored| stored.referendum.state.end().is_some() || stored.block > block);
+        if ongoing && superseded {
+            return;
+        }
+        entries.insert(
+            ref_num,
+            StoredReferendum {
+                referendum,
+                block,
+                fetched_at: now,
+            },
+        );
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let contents = serde_json::to_string(&*self.entries.lock().unwrap())?;
+        cache::write(&self.path, &contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locks::ReferendumState;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn killed(end: u32) -> Referendum {
+        Referendum {
+            state: ReferendumState::Killed { end },
+            tally: None,
+        }
+    }
+
+    fn ongoing() -> Referendum {
+        Referendum {
+            state: ReferendumState::Ongoing {
+                track: 0,
+                submitted: 100,
+                deciding_since: None,
+            },
+            tally: Some((7, 3)),
+        }
+    }
+
+    fn temp_store(name: &str) -> ReferendumStore {
+        let path =
+            std::env::temp_dir().join(format!("locks-report-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        ReferendumStore::open(path)
+    }
+
+    #[tokio::test]
+    async fn concurrent_lookups_fetch_once() {
+        let cache = ReferendumCache::default();
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            Ok(Some(killed(10)))
+        };
+
+        let (a, b) = tokio::join!(cache.get_or_fetch(7, fetch), cache.get_or_fetch(7, fetch));
+        assert_eq!(
+            a.unwrap().unwrap().state,
+            ReferendumState::Killed { end: 10 }
+        );
+        assert_eq!(
+            b.unwrap().unwrap().state,
+            ReferendumState::Killed { end: 10 }
+        );
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_lookups_are_retried() {
+        let cache = ReferendumCache::default();
+        let failed = cache
+            .get_or_fetch(1, || async { Err("connection dropped".into()) })
+            .await;
+        assert!(failed.is_err());
+
+        let retried = cache.get_or_fetch(1, || async { Ok(None) }).await;
+        assert!(retried.unwrap().is_none());
+    }
+
+    #[test]
+    fn finished_referenda_hold_from_their_end() {
+        let store = temp_store("finished");
+        let now = Utc::now();
+        store.insert(5, killed(1_000), 2_000, now);
+
+        let later = now + Duration::days(365);
+        assert!(store.get(5, 1_000, later).is_some());
+        assert!(store.get(5, 3_000, later).is_some());
+        assert!(store.get(5, 999, now).is_none());
+    }
+
+    #[test]
+    fn ongoing_referenda_expire() {
+        let store = temp_store("ongoing");
+        let now = Utc::now();
+        store.insert(5, ongoing(), 2_000, now);
+
+        assert!(store.get(5, 2_000, now + Duration::days(1)).is_some());
+        assert!(store.get(5, 2_010, now + Duration::minutes(1)).is_some());
+        assert!(store.get(5, 2_100, now + Duration::hours(1)).is_none());
+        assert!(store.get(5, 1_990, now).is_none());
+    }
+
+    #[test]
+    fn finished_readings_are_not_replaced_by_ongoing_ones() {
+        let store = temp_store("replace");
+        let now = Utc::now();
+        store.insert(5, killed(1_000), 2_000, now);
+        store.insert(5, ongoing(), 900, now);
+        assert!(store.get(5, 2_000, now).unwrap().state.end().is_some());
+    }
+
+    #[tokio::test]
+    async fn stores_survive_between_runs() {
+        let store = temp_store("reopen");
+        let path = store.path.clone();
+        let cache = ReferendumCache::new(2_000, store);
+        cache
+            .get_or_fetch(5, || async { Ok(Some(killed(1_000))) })
+            .await
+            .unwrap();
+        cache.save().unwrap();
+
+        let cache = ReferendumCache::new(2_500, ReferendumStore::open(path.clone()));
+        let cached = cache
+            .get_or_fetch(5, || async { Err("should not be fetched".into()) })
+            .await;
+        assert_eq!(
+            cached.unwrap().unwrap().state,
+            ReferendumState::Killed { end: 1_000 }
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
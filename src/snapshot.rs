@@ -0,0 +1,257 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use std::str::FromStr;
+use std::sync::Arc;
+use subxt::backend::rpc::RpcClient;
+use subxt::backend::RuntimeVersion;
+use subxt::constants::ConstantsClient;
+use subxt::storage::address::Yes;
+use subxt::storage::{Storage, StorageAddress};
+use subxt::utils::H256;
+use subxt::{Metadata, OnlineClient, PolkadotConfig};
+use tokio::sync::Semaphore;
+
+use crate::clock;
+use crate::connection::NodeConnection;
+use crate::decode::Decoding;
+use crate::referenda::{ReferendumCache, ReferendumStore};
+
+/// Storage queries in flight at once when no limit is configured.
+pub const DEFAULT_PARALLELISM: usize = 8;
+
+/// A past block given by number or hash, as accepted by `--at-block`.
+#[derive(Debug, Clone, Copy)]
+pub enum BlockId {
+    Number(u32),
+    Hash(H256),
+}
+
+impl FromStr for BlockId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("0x") {
+            H256::from_str(s)
+                .map(BlockId::Hash)
+                .map_err(|e| format!("Invalid block hash {}: {}", s, e))
+        } else {
+            s.parse()
+                .map(BlockId::Number)
+                .map_err(|e| format!("Invalid block number {}: {}", s, e))
+        }
+    }
+}
+
+/// A client pinned to one block, the latest finalized one unless a past one was asked
+/// for, so every storage query in a run reads the same state. Storage and constants are
+/// decoded with the metadata of the runtime that block ran, which for past blocks may
+/// predate a runtime upgrade. Queries made through it are limited to `parallelism` at a
+/// time, however many accounts are processed concurrently.
+#[derive(Clone)]
+pub struct Snapshot {
+    api: OnlineClient<PolkadotConfig>,
+    /// The pinned block's runtime, if it differs from the one the node runs now.
+    runtime: Option<(RuntimeVersion, Metadata)>,
+    block: u32,
+    hash: H256,
+    parallelism: usize,
+    requests: Arc<Semaphore>,
+    referenda: Arc<ReferendumCache>,
+}
+
+impl Snapshot {
+    /// Pins the chain's latest finalized block.
+    pub async fn finalized(node: &NodeConnection) -> Result<Self, Box<dyn std::error::Error>> {
+        let hash = node.rpc().chain_get_finalized_head().await?;
+        Snapshot::at_hash(node, hash).await
+    }
+
+    /// Pins a past block. Reading its state needs an archive node.
+    pub async fn at_block(
+        node: &NodeConnection,
+        block: BlockId,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let hash = match block {
+            BlockId::Hash(hash) => hash,
+            BlockId::Number(number) => node
+                .rpc()
+                .chain_get_block_hash(Some(number.into()))
+                .await?
+                .ok_or_else(|| format!("Block {} not found", number))?,
+        };
+        Snapshot::at_hash(node, hash).await
+    }
+
+    /// Pins the last block authored on `date`, in UTC, so the report shows the state at
+    /// the end of that day. Dates that haven't ended yet use the latest finalized block.
+    pub async fn at_end_of(
+        node: &NodeConnection,
+        decoding: Decoding,
+        date: NaiveDate,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let finalized = Snapshot::finalized(node).await?;
+        let next_day = date
+            .succ_opt()
+            .and_then(|day| day.and_hms_opt(0, 0, 0))
+            .map(|midnight| Utc.from_utc_datetime(&midnight))
+            .ok_or_else(|| format!("Invalid date {}", date))?;
+        if next_day > Utc::now() {
+            if date > Utc::now().date_naive() {
+                return Err(format!("{} is in the future", date).into());
+            }
+            return Ok(finalized);
+        }
+
+        let block = clock::last_block_before(
+            node.api(),
+            node.rpc(),
+            decoding,
+            next_day,
+            finalized.block(),
+        )
+        .await?
+        .ok_or_else(|| format!("The chain has no blocks on or before {}", date))?;
+        println!("[Snapshot] Last block on {} is {}", date, block);
+        Snapshot::at_block(node, BlockId::Number(block)).await
+    }
+
+    async fn at_hash(
+        node: &NodeConnection,
+        hash: H256,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let rpc = node.rpc();
+        let header = rpc
+            .chain_get_header(Some(hash))
+            .await?
+            .ok_or_else(|| format!("No header for block {:?}", hash))?;
+        println!("[Snapshot] Block {} ({:?})", header.number, hash);
+
+        let version = rpc.state_get_runtime_version(Some(hash)).await?;
+        let runtime = if version.spec_version == node.api().runtime_version().spec_version {
+            None
+        } else {
+            println!(
+                "[Snapshot] Block {} ran runtime version {}, decoding with its metadata",
+                header.number, version.spec_version
+            );
+            let version = RuntimeVersion {
+                spec_version: version.spec_version,
+                transaction_version: version.transaction_version,
+            };
+            Some((version, rpc.state_get_metadata(Some(hash)).await?))
+        };
+
+        Ok(Snapshot {
+            api: pinned_client(node.api(), node.rpc_client(), runtime.as_ref())?,
+            runtime,
+            block: header.number,
+            hash,
+            parallelism: DEFAULT_PARALLELISM,
+            requests: Arc::new(Semaphore::new(DEFAULT_PARALLELISM)),
+            referenda: Arc::default(),
+        })
+    }
+
+    /// Limits how many storage queries are in flight at once.
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        let parallelism = parallelism.max(1);
+        self.parallelism = parallelism;
+        self.requests = Arc::new(Semaphore::new(parallelism));
+        self
+    }
+
+    /// Reuses referenda read by earlier runs, and keeps the ones read by this run.
+    pub fn with_referendum_store(mut self, store: ReferendumStore) -> Self {
+        self.referenda = Arc::new(ReferendumCache::new(self.block, store));
+        self
+    }
+
+    pub fn parallelism(&self) -> usize {
+        self.parallelism
+    }
+
+    /// Points the snapshot at a new connection after a failover, keeping the same block
+    /// and runtime.
+    pub fn reconnect(&mut self, node: &NodeConnection) -> Result<(), Box<dyn std::error::Error>> {
+        self.api = pinned_client(node.api(), node.rpc_client(), self.runtime.as_ref())?;
+        Ok(())
+    }
+
+    pub fn block(&self) -> u32 {
+        self.block
+    }
+
+    pub fn hash(&self) -> H256 {
+        self.hash
+    }
+
+    /// Storage as of the pinned block.
+    fn storage(&self) -> Storage<PolkadotConfig, OnlineClient<PolkadotConfig>> {
+        self.api.storage().at(self.hash)
+    }
+
+    /// Fetches a storage entry as of the pinned block, waiting for a free request slot.
+    pub async fn fetch<Address>(
+        &self,
+        address: &Address,
+    ) -> Result<Option<Address::Target>, subxt::Error>
+    where
+        Address: StorageAddress<IsFetchable = Yes>,
+    {
+        let _permit = self
+            .requests
+            .acquire()
+            .await
+            .expect("semaphore is never closed");
+        self.storage().fetch(address).await
+    }
+
+    /// Referenda already read at the pinned block.
+    pub fn referenda(&self) -> &ReferendumCache {
+        &self.referenda
+    }
+
+    /// Constants of the pinned block's runtime.
+    pub fn constants(&self) -> ConstantsClient<PolkadotConfig, OnlineClient<PolkadotConfig>> {
+        self.api.constants()
+    }
+
+    /// The client reading the pinned block, for looking up chain parameters in its runtime.
+    pub fn client(&self) -> &OnlineClient<PolkadotConfig> {
+        &self.api
+    }
+}
+
+/// A client decoding with `runtime`, over the same connection as `api`, or `api` itself if
+/// the runtime is the one the node runs now.
+fn pinned_client(
+    api: &OnlineClient<PolkadotConfig>,
+    rpc_client: &RpcClient,
+    runtime: Option<&(RuntimeVersion, Metadata)>,
+) -> Result<OnlineClient<PolkadotConfig>, Box<dyn std::error::Error>> {
+    match runtime {
+        Some((version, metadata)) => Ok(OnlineClient::from_rpc_client_with(
+            api.genesis_hash(),
+            version.clone(),
+            metadata.clone(),
+            rpc_client.clone(),
+        )?),
+        None => Ok(api.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_ids_parse_as_number_or_hash() {
+        assert!(matches!(
+            "18000000".parse(),
+            Ok(BlockId::Number(18_000_000))
+        ));
+        let hash = format!("0x{}", "ab".repeat(32));
+        assert!(matches!(hash.parse(), Ok(BlockId::Hash(h)) if h == H256::repeat_byte(0xab)));
+        assert!("0x12".parse::<BlockId>().is_err());
+        assert!("yesterday".parse::<BlockId>().is_err());
+    }
+}
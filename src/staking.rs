@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+
+/// Era timing needed to place unbonding chunks on the block timeline.
+#[derive(Debug, Clone, Copy)]
+pub struct EraTimings {
+    pub active_era: u32,
+    /// When the active era started, if the chain has recorded it yet.
+    pub active_era_start: Option<DateTime<Utc>>,
+    /// `BondingDuration`, in eras.
+    pub bonding_duration: u32,
+    /// Blocks per era: `EpochDuration` times `SessionsPerEra`.
+    pub era_length: u32,
+}
+
+impl EraTimings {
+    /// Block the given era starts at, counting from the start of the active era. Eras
+    /// that have already started map to the active era's start.
+    pub fn era_start_block(
+        &self,
+        era: u32,
+        current_block: u32,
+        current_time: DateTime<Utc>,
+        ms_per_block: f64,
+    ) -> u32 {
+        let blocks_into_era = match self.active_era_start {
+            Some(start) if ms_per_block > 0.0 => {
+                ((current_time - start).num_milliseconds().max(0) as f64 / ms_per_block) as u32
+            }
+            _ => 0,
+        };
+        let active_era_start_block = current_block.saturating_sub(blocks_into_era);
+        let eras_ahead = era.saturating_sub(self.active_era);
+        active_era_start_block.saturating_add(eras_ahead.saturating_mul(self.era_length))
+    }
+
+    /// Era a chunk unbonded now becomes withdrawable in.
+    pub fn unbond_now_era(&self) -> u32 {
+        self.active_era.saturating_add(self.bonding_duration)
+    }
+}
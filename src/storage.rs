@@ -0,0 +1,102 @@
+use subxt::utils::AccountId32;
+
+/// The balance fields of `System::Account`, in plancks. Runtimes from before the
+/// fungible traits split `frozen` into `misc_frozen` and `fee_frozen`; it is the larger
+/// of the two there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountData {
+    pub free: u128,
+    pub reserved: u128,
+    pub frozen: u128,
+}
+
+/// An entry of `Balances::Locks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceLock {
+    pub id: [u8; 8],
+    pub amount: u128,
+}
+
+/// An entry of `ConvictionVoting::VotingFor`: the account's votes on one track, or its
+/// delegation of that track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Voting {
+    Casting {
+        votes: Vec<(u32, AccountVote)>,
+        prior: PriorLock,
+    },
+    Delegating {
+        balance: u128,
+        target: AccountId32,
+        conviction: u8,
+        prior: PriorLock,
+    },
+}
+
+/// A vote on one referendum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountVote {
+    Standard {
+        aye: bool,
+        conviction: u8,
+        balance: u128,
+    },
+    Split {
+        aye: u128,
+        nay: u128,
+    },
+    SplitAbstain {
+        aye: u128,
+        nay: u128,
+        abstain: u128,
+    },
+}
+
+impl AccountVote {
+    /// A standard vote from its encoded `Vote` byte, which holds the direction in the top
+    /// bit and the conviction in the rest.
+    pub fn standard(vote: u8, balance: u128) -> Self {
+        AccountVote::Standard {
+            aye: vote >= 128,
+            conviction: vote % 128,
+            balance,
+        }
+    }
+}
+
+/// Balance that stays locked until `block` from votes or a delegation already removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PriorLock {
+    pub block: u32,
+    pub balance: u128,
+}
+
+/// The parts of `Staking::Ledger` the report uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StakingLedger {
+    pub total: u128,
+    pub active: u128,
+    pub unlocking: Vec<UnlockChunk>,
+}
+
+/// Balance being unbonded, withdrawable from the start of `era`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnlockChunk {
+    pub value: u128,
+    pub era: u32,
+}
+
+/// `Staking::ActiveEra`, with its start in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveEra {
+    pub index: u32,
+    pub start: Option<u64>,
+}
+
+/// An entry of `Vesting::Vesting`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VestingInfo {
+    pub locked: u128,
+    pub per_block: u128,
+    pub starting_block: u32,
+}
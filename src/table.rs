@@ -0,0 +1,266 @@
+use handlebars::JsonValue;
+
+/// Background colour of empty buckets, as `.none` in `liquidity_matrix.html`.
+const EMPTY_COLOUR: (u8, u8, u8) = (0xFA, 0xFA, 0xFA);
+
+/// A cell of the ladder table, with the bucket class that colours it.
+struct Cell {
+    text: String,
+    class: Option<String>,
+}
+
+impl Cell {
+    fn plain(text: impl Into<String>) -> Self {
+        Cell {
+            text: text.into(),
+            class: None,
+        }
+    }
+}
+
+/// The header line and the rows of the liquidity ladder table.
+fn ladder(report: &JsonValue) -> (Vec<String>, Vec<Vec<Cell>>) {
+    let accounts = report["accounts"].as_array().cloned().unwrap_or_default();
+    let buckets: Vec<String> = report["ladder"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|bucket| bucket["header"].as_str().unwrap_or_default().to_string())
+        .collect();
+
+    let mut header = vec!["Account".to_string(), "Transferable".to_string()];
+    header.extend(buckets);
+
+    let rows = accounts
+        .iter()
+        .map(|account| {
+            let data = &account["data"];
+            // Offline reports may hold accounts read at an earlier block than the title's
+            let mut address = account["address"].as_str().unwrap_or_default().to_string();
+            if let Some(snapshot) = account.get("snapshot") {
+                address.push_str(&format!(" (block {})", snapshot["block"]));
+            }
+            let mut row = vec![
+                Cell::plain(address),
+                Cell::plain(number(&data["balance"]["transferable"])),
+            ];
+            for bucket in data["liquidity"]["locks"].as_array().into_iter().flatten() {
+                let mut text = bucket["amount"].as_str().unwrap_or_default().to_string();
+                if bucket["prior_lock"].as_bool().unwrap_or(false) {
+                    text.push_str(" (prior lock)");
+                }
+                row.push(Cell {
+                    text,
+                    class: bucket["class"].as_str().map(str::to_string),
+                });
+            }
+            row
+        })
+        .collect();
+    (header, rows)
+}
+
+fn number(value: &JsonValue) -> String {
+    value
+        .as_f64()
+        .map(|n| format!("{:.10}", n))
+        .unwrap_or_default()
+}
+
+/// A line above the table saying what it shows.
+fn title(report: &JsonValue) -> String {
+    let title = format!(
+        "{} liquidity ladder at block {}, authored {} UTC",
+        report["chain"]["symbol"].as_str().unwrap_or_default(),
+        report["snapshot"]["block"],
+        report["snapshot"]["date"].as_str().unwrap_or_default(),
+    );
+    // Only offline rebuilds count from anything but the snapshot
+    match report["counted_from"].as_str() {
+        Some(now) => format!("{} (offline, counted from {} UTC)", title, now),
+        None => title,
+    }
+}
+
+/// The liquidity ladder as an aligned table for the terminal. With `colour`, bucket
+/// cells get the report's background colours.
+pub fn terminal(report: &JsonValue, colour: bool) -> String {
+    let (header, rows) = ladder(report);
+    let colours = bucket_colours(report);
+    let mut widths: Vec<usize> = header.iter().map(String::len).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.text.len());
+        }
+    }
+
+    let mut out = format!("{}\n\n", title(report));
+    let header: Vec<String> = header
+        .iter()
+        .zip(&widths)
+        .map(|(label, width)| format!("{:<width$}", label, width = width))
+        .collect();
+    out.push_str(header.join("  ").trim_end());
+    out.push('\n');
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    out.push_str(&rule.join("  "));
+    out.push('\n');
+
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, (cell, width))| {
+                // Addresses read left to right, amounts line up on the right
+                let text = if column == 0 {
+                    format!("{:<width$}", cell.text, width = width)
+                } else {
+                    format!("{:>width$}", cell.text, width = width)
+                };
+                let rgb = cell
+                    .class
+                    .as_deref()
+                    .and_then(|class| colours.iter().find(|(known, _)| known == class))
+                    .map(|(_, rgb)| *rgb);
+                match (colour, rgb) {
+                    (true, Some((r, g, b))) => {
+                        format!("\x1b[48;2;{};{};{}m\x1b[30m{}\x1b[0m", r, g, b, text)
+                    }
+                    _ => text,
+                }
+            })
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Background colours by bucket class, from the report's ladder.
+fn bucket_colours(report: &JsonValue) -> Vec<(String, (u8, u8, u8))> {
+    let mut colours = vec![("none".to_string(), EMPTY_COLOUR)];
+    for bucket in report["ladder"].as_array().into_iter().flatten() {
+        if let (Some(class), Some(rgb)) = (
+            bucket["class"].as_str(),
+            bucket["colour"].as_str().and_then(parse_colour),
+        ) {
+            colours.push((class.to_string(), rgb));
+        }
+    }
+    colours
+}
+
+/// Parses a CSS colour like `#F44336`.
+fn parse_colour(colour: &str) -> Option<(u8, u8, u8)> {
+    let hex = colour.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |at: usize| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// The liquidity ladder as a GitHub flavoured Markdown table.
+pub fn markdown(report: &JsonValue) -> String {
+    let (header, rows) = ladder(report);
+    let mut out = format!("**{}**\n\n", title(report));
+    out.push_str(&format!("| {} |\n", header.join(" | ")));
+    let alignments: Vec<&str> = (0..header.len())
+        .map(|column| if column == 0 { "---" } else { "---:" })
+        .collect();
+    out.push_str(&format!("| {} |\n", alignments.join(" | ")));
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                let text = cell.text.replace('|', "\\|");
+                if column == 0 {
+                    format!("`{}`", text)
+                } else {
+                    text
+                }
+            })
+            .collect();
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn report() -> JsonValue {
+        json!({
+            "chain": { "symbol": "DOT" },
+            "snapshot": { "block": 100, "date": "2024-01-01 00:00:00" },
+            "ladder": [
+                { "label": "Locked 60+ Days", "header": "Locked 60+ Days", "class": "locked-60-plus-days", "colour": "#F44336" },
+                { "label": "Locked 0 Days", "header": "Ready to Unlock", "class": "locked-0-days", "colour": "#FFEBEE" },
+            ],
+            "accounts": [{
+                "address": "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5",
+                "data": {
+                    "balance": { "transferable": 2.5 },
+                    "liquidity": { "locks": [
+                        { "lock_category": "Locked 60+ Days", "amount": "10.0000000000", "class": "locked-60-plus-days", "prior_lock": false },
+                        { "lock_category": "Locked 0 Days", "amount": "none", "class": "none", "prior_lock": false },
+                    ]},
+                },
+            }],
+        })
+    }
+
+    #[test]
+    fn terminal_table_aligns_columns() {
+        let table = terminal(&report(), false);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(
+            lines[0],
+            "DOT liquidity ladder at block 100, authored 2024-01-01 00:00:00 UTC"
+        );
+        assert!(lines[2].starts_with("Account "));
+        assert!(lines[2].ends_with("Locked 60+ Days  Ready to Unlock"));
+        assert_eq!(lines[3].len(), lines[4].len());
+        assert!(lines[4].ends_with("   10.0000000000             none"));
+    }
+
+    #[test]
+    fn terminal_table_colours_buckets() {
+        let table = terminal(&report(), true);
+        assert!(table.contains("\x1b[48;2;244;67;54m\x1b[30m  10.0000000000\x1b[0m"));
+        assert!(!terminal(&report(), false).contains('\x1b'));
+    }
+
+    #[test]
+    fn offline_title_says_when_it_counts_from() {
+        let mut report = report();
+        report["counted_from"] = "2024-03-01 12:00:00".into();
+        assert_eq!(
+            title(&report),
+            "DOT liquidity ladder at block 100, authored 2024-01-01 00:00:00 UTC \
+             (offline, counted from 2024-03-01 12:00:00 UTC)"
+        );
+    }
+
+    #[test]
+    fn colours_parse_as_css_hex() {
+        assert_eq!(parse_colour("#F44336"), Some((0xF4, 0x43, 0x36)));
+        assert_eq!(parse_colour("red"), None);
+    }
+
+    #[test]
+    fn markdown_table() {
+        let table = markdown(&report());
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(
+            lines[2],
+            "| Account | Transferable | Locked 60+ Days | Ready to Unlock |"
+        );
+        assert_eq!(lines[3], "| --- | ---: | ---: | ---: |");
+        assert_eq!(
+            lines[4],
+            "| `15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5` | 2.5000000000 | 10.0000000000 | none |"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use chrono::{DateTime, Duration, Utc};
+use handlebars::JsonValue;
+use std::fmt::Write;
+
+const WIDTH: f64 = 960.0;
+/// Space left of the plot for row labels.
+const LABEL_WIDTH: f64 = 180.0;
+const PADDING: f64 = 10.0;
+const ROW_HEIGHT: f64 = 14.0;
+const ROW_GAP: f64 = 4.0;
+const CURVE_HEIGHT: f64 = 120.0;
+const AXIS_HEIGHT: f64 = 24.0;
+const TICKS: i32 = 5;
+
+/// Bar colours by `LockSource`.
+const SOURCE_COLOURS: [(&str, &str); 6] = [
+    ("vote", "#FF7043"),
+    ("delegation", "#AB47BC"),
+    ("prior_lock", "#8D6E63"),
+    ("staking_bonded", "#42A5F5"),
+    ("staking_unbonding", "#90CAF9"),
+    ("vesting", "#66BB6A"),
+];
+
+/// A locked interval as read back from the report model.
+struct Bar {
+    label: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    /// Where a linear release starts.
+    linear_from: Option<DateTime<Utc>>,
+    amount: f64,
+    colour: &'static str,
+}
+
+fn date(value: &JsonValue) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value.as_str()?)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+fn bar(interval: &JsonValue) -> Option<Bar> {
+    let source = interval["source"].as_str()?;
+    let label = match source {
+        "vote" => format!("Referendum #{}", interval["referendum"]),
+        "delegation" => format!("Delegation, track {}", interval["track"]),
+        "prior_lock" => format!("Prior lock, track {}", interval["track"]),
+        "staking_bonded" => "Staking, bonded".to_string(),
+        "staking_unbonding" => format!("Unbonding, era {}", interval["era"]),
+        "vesting" => format!("Vesting from #{}", interval["starting_block"]),
+        other => other.replace('_', " "),
+    };
+    let colour = SOURCE_COLOURS
+        .iter()
+        .find(|(known, _)| *known == source)
+        .map_or("#BDBDBD", |(_, colour)| colour);
+    Some(Bar {
+        label,
+        start: date(&interval["start_date"])?,
+        end: date(&interval["end_date"])?,
+        linear_from: date(&interval["release"]["from"]),
+        amount: interval["amount"].as_f64()?,
+        colour,
+    })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Draws an account's locked intervals as a Gantt chart from `now`, above its
+/// `locked_over_time` curve, as an inline SVG.
+pub fn svg(data: &JsonValue, now: DateTime<Utc>, symbol: &str) -> String {
+    let mut bars: Vec<Bar> = data["intervals"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(bar)
+        .collect();
+    bars.sort_by_key(|bar| bar.end);
+    let points: Vec<(DateTime<Utc>, f64)> = data["locked_over_time"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|point| Some((date(&point["date"])?, point["amount"].as_f64()?)))
+        .collect();
+
+    let horizon = bars
+        .iter()
+        .map(|bar| bar.end)
+        .max()
+        .unwrap_or(now)
+        .max(now + Duration::days(1));
+    let span = (horizon - now).num_seconds() as f64;
+    let plot_width = WIDTH - LABEL_WIDTH - PADDING;
+    let x = |date: DateTime<Utc>| {
+        let offset = (date - now).num_seconds() as f64 / span;
+        LABEL_WIDTH + plot_width * offset.clamp(0.0, 1.0)
+    };
+
+    let bars_height = bars.len() as f64 * (ROW_HEIGHT + ROW_GAP);
+    let curve_top = PADDING + bars_height + PADDING;
+    let axis_top = curve_top + CURVE_HEIGHT;
+    let height = axis_top + AXIS_HEIGHT;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg class="timeline" xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {w} {h}" width="{w}" height="{h}" font-family="Arial, sans-serif" font-size="11">"#,
+        w = WIDTH,
+        h = height
+    );
+
+    // Date grid across bars and curve
+    for tick in 0..=TICKS {
+        let date = now + (horizon - now) * tick / TICKS;
+        let tick_x = x(date);
+        let anchor = match tick {
+            0 => "start",
+            TICKS => "end",
+            _ => "middle",
+        };
+        let _ = write!(
+            svg,
+            r##"<line x1="{x:.1}" y1="{top}" x2="{x:.1}" y2="{bottom}" stroke="#E0E0E0"/><text x="{x:.1}" y="{label}" text-anchor="{anchor}" fill="#616161">{date}</text>"##,
+            x = tick_x,
+            top = PADDING,
+            bottom = axis_top,
+            label = axis_top + 16.0,
+            anchor = anchor,
+            date = date.format("%Y-%m-%d"),
+        );
+    }
+
+    for (row, bar) in bars.iter().enumerate() {
+        let top = PADDING + row as f64 * (ROW_HEIGHT + ROW_GAP);
+        let bottom = top + ROW_HEIGHT;
+        let start = x(bar.start.max(now));
+        // Intervals already over still get a sliver, so they can be seen and hovered
+        let end = x(bar.end).max(start + 2.0);
+        let title = format!(
+            "{}: {:.10} {}, unlocks {}",
+            bar.label,
+            bar.amount,
+            symbol,
+            bar.end.format("%Y-%m-%d %H:%M")
+        );
+        let _ = write!(
+            svg,
+            r##"<text x="{lx}" y="{ty:.1}" fill="#212121">{label}</text>"##,
+            lx = PADDING,
+            ty = bottom - 3.0,
+            label = escape(&bar.label),
+        );
+        let shape = match bar.linear_from {
+            // Linear releases taper off from where they start releasing
+            Some(from) => format!(
+                r#"<polygon points="{start:.1},{top:.1} {from:.1},{top:.1} {end:.1},{bottom:.1} {start:.1},{bottom:.1}""#,
+                start = start,
+                from = x(from.max(now)).min(end),
+                end = end,
+                top = top,
+                bottom = bottom,
+            ),
+            None => format!(
+                r#"<rect x="{start:.1}" y="{top:.1}" width="{width:.1}" height="{height}""#,
+                start = start,
+                top = top,
+                width = end - start,
+                height = ROW_HEIGHT,
+            ),
+        };
+        let _ = write!(
+            svg,
+            r#"{shape} fill="{colour}"><title>{title}</title></{tag}>"#,
+            shape = shape,
+            colour = bar.colour,
+            title = escape(&title),
+            tag = if bar.linear_from.is_some() {
+                "polygon"
+            } else {
+                "rect"
+            },
+        );
+    }
+
+    // Locked balance over time, as a step curve
+    let max = points.iter().map(|(_, amount)| *amount).fold(0.0, f64::max);
+    let y = |amount: f64| {
+        if max > 0.0 {
+            curve_top + CURVE_HEIGHT * (1.0 - amount / max)
+        } else {
+            axis_top
+        }
+    };
+    if let Some(&(first, amount)) = points.first() {
+        let mut path = format!("M{:.1},{:.1} V{:.1}", x(first), axis_top, y(amount));
+        for (date, amount) in &points[1..] {
+            let _ = write!(path, " H{:.1} V{:.1}", x(*date), y(*amount));
+        }
+        let _ = write!(path, " H{:.1} V{:.1} Z", x(horizon), axis_top);
+        let _ = write!(
+            svg,
+            r##"<path d="{path}" fill="#FFCCBC" stroke="#F4511E" stroke-width="1.5"><title>Locked balance</title></path>"##,
+            path = path,
+        );
+    }
+    let _ = write!(
+        svg,
+        r##"<text x="{lx}" y="{top:.1}" fill="#212121">Locked balance</text><text x="{lx}" y="{mid:.1}" fill="#616161">max {max:.2} {symbol}</text><line x1="{px}" y1="{axis:.1}" x2="{right}" y2="{axis:.1}" stroke="#9E9E9E"/>"##,
+        lx = PADDING,
+        top = curve_top + 12.0,
+        mid = curve_top + 28.0,
+        max = max,
+        symbol = escape(symbol),
+        px = LABEL_WIDTH,
+        right = WIDTH - PADDING,
+        axis = axis_top,
+    );
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn draws_a_row_per_interval_and_the_curve() {
+        let now = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let data = json!({
+            "intervals": [
+                {
+                    "source": "vesting", "starting_block": 10, "amount": 100.0,
+                    "start_date": "2024-01-01T00:00:00Z", "end_date": "2024-03-01T00:00:00Z",
+                    "release": { "release": "linear", "from": "2024-02-01T00:00:00Z" },
+                },
+                {
+                    "source": "vote", "referendum": 120, "amount": 5.0,
+                    "start_date": "2023-12-01T00:00:00Z", "end_date": "2024-01-15T00:00:00Z",
+                    "release": { "release": "at_end" },
+                },
+            ],
+            "locked_over_time": [
+                { "date": "2024-01-01T00:00:00Z", "amount": 100.0 },
+                { "date": "2024-03-01T00:00:00Z", "amount": 0.0 },
+            ],
+        });
+        let svg = svg(&data, now, "DOT");
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+        // Sorted by end date: the vote first, then vesting as a tapering polygon
+        assert!(svg.find("Referendum #120").unwrap() < svg.find("Vesting from #10").unwrap());
+        assert_eq!(svg.matches("<rect").count(), 1);
+        assert_eq!(svg.matches("<polygon").count(), 1);
+        assert!(svg.contains("max 100.00 DOT"));
+        assert!(svg.contains(
+            "<title>Referendum #120: 5.0000000000 DOT, unlocks 2024-01-15 00:00</title>"
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+use serde::Serialize;
+
+/// Balance lock IDs the report knows about, with the pallet that sets them and the header
+/// of their column, in the order their columns are shown.
+const KNOWN_LOCKS: [(&[u8; 8], &str, &str, &str); 5] = [
+    (
+        b"pyconvot",
+        "ConvictionVoting",
+        "Conviction voting",
+        "Conviction Voting Lock",
+    ),
+    (b"staking ", "Staking", "Staking", "Staking Lock"),
+    (b"vesting ", "Vesting", "Vesting", "Vesting Lock"),
+    (b"democrac", "Democracy", "Democracy", "Democracy Lock"),
+    (
+        b"phrelect",
+        "PhragmenElection",
+        "Elections",
+        "Elections Lock",
+    ),
+];
+
+/// Label for locks with an ID outside `KNOWN_LOCKS`.
+const OTHER_LABEL: &str = "Other";
+const OTHER_HEADER: &str = "Other Locks";
+
+/// A single `BalanceLock`, with its amount in the chain's token.
+#[derive(Debug, Clone, Serialize)]
+pub struct LockTotal {
+    pub id: String,
+    pub label: &'static str,
+    pub amount: f64,
+    /// Whether this is the largest lock, which is the one that sets the frozen balance.
+    pub dominant: bool,
+}
+
+/// One column of the report's lock breakdown.
+#[derive(Debug, Clone, Serialize)]
+pub struct LockColumn {
+    pub label: &'static str,
+    /// Amount in the chain's token, formatted like the liquidity ladder, or "none".
+    pub amount: String,
+    pub dominant: bool,
+}
+
+/// An account's balance locks. Locks overlap rather than add up, so the frozen balance is
+/// the largest of them.
+#[derive(Debug, Clone, Serialize)]
+pub struct LockTotals {
+    pub locks: Vec<LockTotal>,
+    pub frozen: f64,
+    pub columns: Vec<LockColumn>,
+}
+
+impl LockTotals {
+    pub fn new(locks: impl IntoIterator<Item = ([u8; 8], f64)>) -> Self {
+        let mut locks: Vec<LockTotal> = locks
+            .into_iter()
+            .map(|(id, amount)| LockTotal {
+                id: String::from_utf8_lossy(&id).into_owned(),
+                label: label(&id),
+                amount,
+                dominant: false,
+            })
+            .collect();
+
+        let frozen = locks.iter().map(|lock| lock.amount).fold(0.0, f64::max);
+        if let Some(lock) = locks
+            .iter_mut()
+            .filter(|lock| lock.amount > 0.0)
+            .max_by(|a, b| a.amount.total_cmp(&b.amount))
+        {
+            lock.dominant = true;
+        }
+
+        let labels = KNOWN_LOCKS
+            .iter()
+            .map(|(_, _, label, _)| *label)
+            .chain(std::iter::once(OTHER_LABEL));
+        let columns = labels
+            .map(|label| {
+                let matching = locks.iter().filter(|lock| lock.label == label);
+                let amount = matching.clone().map(|lock| lock.amount).reduce(f64::max);
+                LockColumn {
+                    label,
+                    amount: amount.map_or("none".to_string(), |amount| format!("{:.10}", amount)),
+                    dominant: matching.clone().any(|lock| lock.dominant),
+                }
+            })
+            .collect();
+
+        LockTotals {
+            locks,
+            frozen,
+            columns,
+        }
+    }
+
+    /// Totals for an account without locks, with every column empty.
+    pub fn empty() -> Self {
+        LockTotals::new([])
+    }
+
+    pub fn dominant(&self) -> Option<&LockTotal> {
+        self.locks.iter().find(|lock| lock.dominant)
+    }
+}
+
+/// Headers of the lock breakdown columns, in the same order as `LockTotals::columns`.
+pub fn column_headers() -> Vec<&'static str> {
+    KNOWN_LOCKS
+        .iter()
+        .map(|(_, _, _, header)| *header)
+        .chain(std::iter::once(OTHER_HEADER))
+        .collect()
+}
+
+/// Human readable name of a lock ID.
+pub fn label(id: &[u8; 8]) -> &'static str {
+    KNOWN_LOCKS
+        .iter()
+        .find(|(known, _, _, _)| *known == id)
+        .map_or(OTHER_LABEL, |(_, _, label, _)| label)
+}
+
+/// Pallet that sets a lock ID, if it is a known one.
+pub fn pallet(id: &[u8; 8]) -> Option<&'static str> {
+    KNOWN_LOCKS
+        .iter()
+        .find(|(known, _, _, _)| *known == id)
+        .map(|(_, pallet, _, _)| *pallet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn largest_lock_dominates() {
+        let totals = LockTotals::new([
+            (*b"pyconvot", 10.0),
+            (*b"staking ", 25.0),
+            (*b"vesting ", 5.0),
+        ]);
+        assert_eq!(totals.frozen, 25.0);
+        assert_eq!(
+            totals.dominant().map(|lock| lock.id.as_str()),
+            Some("staking ")
+        );
+        assert_eq!(totals.locks.iter().filter(|lock| lock.dominant).count(), 1);
+    }
+
+    #[test]
+    fn columns_cover_known_and_other_locks() {
+        let totals = LockTotals::new([(*b"pyconvot", 1.5), (*b"somelock", 2.0)]);
+        let columns: Vec<_> = totals
+            .columns
+            .iter()
+            .map(|column| (column.label, column.amount.as_str(), column.dominant))
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                ("Conviction voting", "1.5000000000", false),
+                ("Staking", "none", false),
+                ("Vesting", "none", false),
+                ("Democracy", "none", false),
+                ("Elections", "none", false),
+                ("Other", "2.0000000000", true),
+            ]
+        );
+        assert_eq!(column_headers().len(), totals.columns.len());
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// A vesting schedule as reported, with amounts in the chain's token.
+#[derive(Debug, Clone, Serialize)]
+pub struct VestingSchedule {
+    pub locked: f64,
+    pub per_block: f64,
+    pub starting_block: u32,
+    pub ending_block: u32,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    /// Amount vested so far according to the schedule.
+    pub unlocked_to_date: f64,
+    /// Amount the schedule still locks at the current block.
+    pub still_locked: f64,
+}
+
+/// All of an account's vesting schedules.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VestingSummary {
+    pub schedules: Vec<VestingSchedule>,
+    /// Vested balance still held by the `vesting ` lock until `vest()` is called. The
+    /// chain doesn't record what was claimed from each schedule, so this is only known for
+    /// all of them together.
+    pub claimable: f64,
+}
+
+/// Balance a schedule still locks at `block`, following `VestingInfo::locked_at`: fully
+/// locked until the starting block, then released linearly at `per_block`.
+pub fn locked_at(locked: u128, per_block: u128, starting_block: u32, block: u32) -> u128 {
+    let vested_blocks = block.saturating_sub(starting_block) as u128;
+    locked.saturating_sub(per_block.saturating_mul(vested_blocks))
+}
+
+/// First block at which the schedule has released everything.
+pub fn ending_block(locked: u128, per_block: u128, starting_block: u32) -> u32 {
+    let duration = if per_block == 0 {
+        u32::MAX as u128
+    } else {
+        locked.div_ceil(per_block)
+    };
+    starting_block.saturating_add(duration.min(u32::MAX as u128) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_until_start_then_linear() {
+        assert_eq!(locked_at(1_000, 10, 100, 50), 1_000);
+        assert_eq!(locked_at(1_000, 10, 100, 100), 1_000);
+        assert_eq!(locked_at(1_000, 10, 100, 150), 500);
+        assert_eq!(locked_at(1_000, 10, 100, 250), 0);
+    }
+
+    #[test]
+    fn ending_block_rounds_up() {
+        assert_eq!(ending_block(1_000, 10, 100), 200);
+        assert_eq!(ending_block(1_001, 10, 100), 201);
+        assert_eq!(ending_block(1_000, 0, 100), u32::MAX);
+    }
+}